@@ -0,0 +1,29 @@
+extern crate kappa_lisp;
+use kappa_lisp::env::Env;
+use kappa_lisp::kappa_lisp::{init, run};
+use std::time::{Duration, Instant};
+
+// times `(fib 30)`, which spends most of its time in function calls and variable lookups.
+// Prints the fastest of a few runs.
+fn main() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(
+        &mut env,
+        r"
+(defun fib (n)
+  (if (< n 2)
+      1
+      (+ (fib (- n 1)) (fib (- n 2)))))
+",
+    )
+    .unwrap();
+    let mut best = Duration::from_secs(u64::MAX);
+    for _ in 0..3 {
+        let start = Instant::now();
+        let expr = run(&mut env, "(fib 30)").unwrap();
+        best = best.min(start.elapsed());
+        assert_eq!(expr.to_string(), "1346269");
+    }
+    println!("(fib 30): {:?}", best);
+}
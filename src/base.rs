@@ -1,10 +1,50 @@
+use std::cmp::Ordering;
 use std::ops::Deref;
 
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+
 use env::Env;
 use eval::funcall;
 use expr::{Error as E, Expr, Kfloat, Kint, Result, Type};
+use hash::{is_eq, is_eql, is_equal};
+use print;
+use symbol;
 use util::*;
 
+fn to_float(x: &Expr) -> Result<Kfloat> {
+    match x {
+        &Expr::Int(x) => Ok(x as Kfloat),
+        Expr::Bignum(x) => Ok(x.to_f64().unwrap_or(Kfloat::NAN)),
+        &Expr::Float(x) => Ok(x),
+        x => Err(E::Type(Type::Number, x.clone())),
+    }
+}
+
+fn to_bigint(x: &Expr) -> Result<BigInt> {
+    match x {
+        &Expr::Int(x) => Ok(BigInt::from(x)),
+        Expr::Bignum(x) => Ok(x.deref().clone()),
+        x => Err(E::Type(Type::Int, x.clone())),
+    }
+}
+
+// applies an operation on integers. `small` returns None when the result doesn't fit in a fixnum,
+// and then `big` computes it on bignums.
+fn int_op<S, B>(x: &Expr, y: &Expr, small: S, big: B) -> Result<Expr>
+where
+    S: Fn(Kint, Kint) -> Option<Kint>,
+    B: Fn(BigInt, BigInt) -> BigInt,
+{
+    if let (&Expr::Int(x), &Expr::Int(y)) = (x, y) {
+        if let Some(z) = small(x, y) {
+            return Ok(kint(z));
+        }
+    }
+    Ok(kbigint(big(to_bigint(x)?, to_bigint(y)?)))
+}
+
 // since rust's macro cannot treat binop, work around macro is needed.
 macro_rules! expr {
     ($e:expr) => {
@@ -12,8 +52,9 @@ macro_rules! expr {
     };
 }
 
+// integers overflowing a fixnum are promoted to bignums
 macro_rules! def_arith_op {
-    ($name: ident, $op: tt, $init: expr) => {
+    ($name: ident, $op: tt, $checked: ident, $init: expr) => {
         pub fn $name(env: &mut Env, args: &Expr) -> Result<Expr> {
             let (init, args) = match args {
                 Expr::Cons(hd, tl) => match tl.deref() {
@@ -25,12 +66,9 @@ macro_rules! def_arith_op {
             f_foldl(
                 env,
                 &|_, x, y| match (x, y) {
-                    (&Expr::Int(x), &Expr::Int(y)) => Ok(kint(expr!(x $op y))),
-                    (&Expr::Float(x), &Expr::Int(y)) => Ok(kfloat(expr!(x $op (y as Kfloat)))),
-                    (&Expr::Int(x), &Expr::Float(y)) => Ok(kfloat(expr!((x as Kfloat) $op y))),
-                    (&Expr::Float(x), &Expr::Float(y)) => Ok(kfloat(expr!(x $op y))),
-                    (&Expr::Int(_), y) => Err(E::Type(Type::Int, y.clone())),
-                    (x, _) => Err(E::Type(Type::Int, x.clone())),
+                    (&Expr::Float(x), y) => Ok(kfloat(expr!(x $op to_float(y)?))),
+                    (x, &Expr::Float(y)) => Ok(kfloat(expr!(to_float(x)? $op y))),
+                    (x, y) => int_op(x, y, |x, y| x.$checked(y), |x, y| expr!(x $op y)),
                 },
                 &init,
                 args,
@@ -39,39 +77,363 @@ macro_rules! def_arith_op {
     };
 }
 
-def_arith_op!(k_add, +, kint(0));
-def_arith_op!(k_sub, -, kint(0));
-def_arith_op!(k_mul, *, kint(1));
-def_arith_op!(k_div, /, kint(1));
+def_arith_op!(k_add, +, checked_add, kint(0));
+def_arith_op!(k_sub, -, checked_sub, kint(0));
+def_arith_op!(k_mul, *, checked_mul, kint(1));
+
+// compares two numbers. An integer compared with a float is converted to a float.
+fn compare_numbers(x: &Expr, y: &Expr) -> Result<Option<Ordering>> {
+    match (x, y) {
+        (&Expr::Int(x), &Expr::Int(y)) => Ok(x.partial_cmp(&y)),
+        (&Expr::Float(_), _) | (_, &Expr::Float(_)) => Ok(to_float(x)?.partial_cmp(&to_float(y)?)),
+        (x, y) => Ok(Some(to_bigint(x)?.cmp(&to_bigint(y)?))),
+    }
+}
+
+// the arguments of a function that takes one or more numbers
+fn numbers(args: &Expr) -> Result<Vec<&Expr>> {
+    let mut numbers = Vec::new();
+    let mut head = args;
+    while let Expr::Cons(x, rest) = head {
+        match x.deref() {
+            x @ Expr::Int(_) | x @ Expr::Bignum(_) | x @ Expr::Float(_) => numbers.push(x),
+            x => return Err(E::Type(Type::Number, x.clone())),
+        }
+        head = rest.deref();
+    }
+    match head {
+        Expr::Nil if numbers.is_empty() => Err(E::ArityShort),
+        Expr::Nil => Ok(numbers),
+        _ => Err(E::InvalidArgument(args.clone())),
+    }
+}
+
+// whether `test` holds for each adjacent pair of the arguments. NaN is not ordered.
+fn f_compare<F>(args: &Expr, test: F) -> Result<Expr>
+where
+    F: Fn(Ordering) -> bool,
+{
+    let numbers = numbers(args)?;
+    for pair in numbers.windows(2) {
+        match compare_numbers(pair[0], pair[1])? {
+            Some(ordering) if test(ordering) => (),
+            _ => return Ok(kbool(false)),
+        }
+    }
+    Ok(kbool(true))
+}
 
-// FIXME: accept multiple arguments
 macro_rules! def_arith_cmp {
-    ($name: ident, $op: tt) => {
+    ($name: ident, $test: expr) => {
+        pub fn $name(_: &mut Env, args: &Expr) -> Result<Expr> {
+            f_compare(args, $test)
+        }
+    };
+}
+
+def_arith_cmp!(k_gt, |o| o == Ordering::Greater);
+def_arith_cmp!(k_ge, |o| o != Ordering::Less);
+def_arith_cmp!(k_lt, |o| o == Ordering::Less);
+def_arith_cmp!(k_le, |o| o != Ordering::Greater);
+def_arith_cmp!(k_eq, |o| o == Ordering::Equal);
+
+// true if no two arguments are equal
+pub fn k_neq(_: &mut Env, args: &Expr) -> Result<Expr> {
+    let numbers = numbers(args)?;
+    for (i, x) in numbers.iter().enumerate() {
+        for y in &numbers[i + 1..] {
+            if compare_numbers(x, y)? == Some(Ordering::Equal) {
+                return Ok(kbool(false));
+            }
+        }
+    }
+    Ok(kbool(true))
+}
+
+// the greatest (`ordering` is `Greater`) or the least argument.
+// The result is a float if any of the arguments is a float.
+fn f_extremum(args: &Expr, ordering: Ordering) -> Result<Expr> {
+    let numbers = numbers(args)?;
+    let mut extremum = numbers[0];
+    for x in &numbers[1..] {
+        match compare_numbers(x, extremum)? {
+            Some(o) if o == ordering => extremum = x,
+            Some(_) => (),
+            // NaN wins
+            None => {
+                if let Expr::Float(f) = x {
+                    if f.is_nan() {
+                        extremum = x;
+                    }
+                }
+            }
+        }
+    }
+    if numbers.iter().any(|x| matches!(x, Expr::Float(_))) {
+        Ok(kfloat(to_float(extremum)?))
+    } else {
+        Ok(extremum.clone())
+    }
+}
+
+pub fn k_max(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_extremum(args, Ordering::Greater)
+}
+
+pub fn k_min(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_extremum(args, Ordering::Less)
+}
+
+pub fn k_zerop(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    Ok(kbool(
+        compare_numbers(x, &kint(0))? == Some(Ordering::Equal),
+    ))
+}
+
+pub fn k_plusp(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    Ok(kbool(
+        compare_numbers(x, &kint(0))? == Some(Ordering::Greater),
+    ))
+}
+
+pub fn k_minusp(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    Ok(kbool(compare_numbers(x, &kint(0))? == Some(Ordering::Less)))
+}
+
+// unlike the others, not an error for non-numbers
+pub fn k_natnump(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    Ok(kbool(match x {
+        &Expr::Int(x) => x >= 0,
+        Expr::Bignum(x) => x.is_positive(),
+        _ => false,
+    }))
+}
+
+pub fn k_add1(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    match x {
+        &Expr::Float(x) => Ok(kfloat(x + 1.0)),
+        x => int_op(x, &kint(1), Kint::checked_add, |x, y| x + y),
+    }
+}
+
+pub fn k_sub1(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    match x {
+        &Expr::Float(x) => Ok(kfloat(x - 1.0)),
+        x => int_op(x, &kint(1), Kint::checked_sub, |x, y| x - y),
+    }
+}
+
+pub fn k_abs(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    match x {
+        &Expr::Float(x) => Ok(kfloat(x.abs())),
+        x => int_op(x, x, |x, _| x.checked_abs(), |x, _| x.abs()),
+    }
+}
+
+/// the error signaled by division by zero
+pub fn arith_error() -> E {
+    E::Signal(ksym("arith-error"), knil())
+}
+
+// integers are divided with truncation. If any argument is a float, all of them are
+// divided as floats, and dividing by zero gives an infinity or NaN.
+pub fn k_div(_: &mut Env, args: &Expr) -> Result<Expr> {
+    if args == &Expr::Nil {
+        return Ok(kint(1));
+    }
+    let numbers = numbers(args)?;
+    let (init, divisors) = match numbers.len() {
+        1 => (&Expr::Int(1), &numbers[..]),
+        _ => (numbers[0], &numbers[1..]),
+    };
+    if numbers.iter().any(|x| matches!(x, Expr::Float(_))) {
+        let mut acc = to_float(init)?;
+        for x in divisors {
+            acc /= to_float(x)?;
+        }
+        return Ok(kfloat(acc));
+    }
+    let mut acc = init.clone();
+    for x in divisors {
+        if let Expr::Int(0) = x {
+            return Err(arith_error());
+        }
+        acc = int_op(&acc, x, Kint::checked_div, |x, y| x / y)?;
+    }
+    Ok(acc)
+}
+
+// the remainder of truncating division. Its sign is the dividend's.
+pub fn k_rem(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any)(y, Any));
+    match (x, y) {
+        (_, Expr::Int(0)) => Err(arith_error()),
+        (x, y) => int_op(x, y, Kint::checked_rem, |x, y| x % y),
+    }
+}
+
+// the remainder of flooring division. Its sign is the divisor's.
+pub fn k_mod(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any)(y, Any));
+    match (x, y) {
+        (&Expr::Float(_), _) | (_, &Expr::Float(_)) => {
+            let (x, y) = (to_float(x)?, to_float(y)?);
+            let r = x % y;
+            Ok(kfloat(if r != 0.0 && (r < 0.0) != (y < 0.0) {
+                r + y
+            } else {
+                r
+            }))
+        }
+        (_, Expr::Int(0)) => Err(arith_error()),
+        (x, y) => int_op(
+            x,
+            y,
+            |x, y| {
+                let r = x.checked_rem(y)?;
+                Some(if r != 0 && (r < 0) != (y < 0) {
+                    r + y
+                } else {
+                    r
+                })
+            },
+            |x, y| x.mod_floor(&y),
+        ),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rounding {
+    Floor,
+    Ceiling,
+    Round,
+    Truncate,
+}
+
+impl Rounding {
+    // rounds `x / y` of integers. `y` is not zero.
+    fn div_int(self, x: BigInt, y: BigInt) -> BigInt {
+        let (q, r) = x.div_rem(&y);
+        if r.is_zero() {
+            return q;
+        }
+        // the direction to the other candidate of the quotient
+        let away = if r.is_negative() != y.is_negative() {
+            BigInt::from(-1)
+        } else {
+            BigInt::from(1)
+        };
+        match self {
+            Rounding::Floor if away.is_negative() => q + away,
+            Rounding::Ceiling if away.is_positive() => q + away,
+            Rounding::Round => {
+                let (r2, y) = (r.abs() * 2, y.abs());
+                if r2 > y || (r2 == y && q.is_odd()) {
+                    q + away
+                } else {
+                    q
+                }
+            }
+            _ => q,
+        }
+    }
+
+    // ties of `round` go to the even integer
+    fn float(self, x: Kfloat) -> Kfloat {
+        match self {
+            Rounding::Floor => x.floor(),
+            Rounding::Ceiling => x.ceil(),
+            Rounding::Round => x.round_ties_even(),
+            Rounding::Truncate => x.trunc(),
+        }
+    }
+}
+
+// `floor`, `ceiling`, `round` and `truncate` with an optional divisor
+fn f_round_to_int(args: &Expr, rounding: Rounding) -> Result<Expr> {
+    get_args!(args, (x, Any) & optional(y, Any));
+    let y = match y {
+        Some(Expr::Nil) | None => None,
+        Some(y) => Some(y),
+    };
+    let f = match (x, y) {
+        (&Expr::Float(x), None) => x,
+        (x, None) => return Ok(kbigint(to_bigint(x)?)),
+        (&Expr::Float(_), Some(y)) | (_, Some(y @ &Expr::Float(_))) => {
+            let (x, y) = (to_float(x)?, to_float(y)?);
+            if y == 0.0 {
+                return Err(arith_error());
+            }
+            x / y
+        }
+        (_, Some(&Expr::Int(0))) => return Err(arith_error()),
+        (x, Some(y)) => return Ok(kbigint(rounding.div_int(to_bigint(x)?, to_bigint(y)?))),
+    };
+    // a float too large for a fixnum becomes a bignum
+    match BigInt::from_f64(rounding.float(f)) {
+        Some(i) => Ok(kbigint(i)),
+        None => Err(E::Signal(ksym("overflow-error"), knil())),
+    }
+}
+
+pub fn k_floor(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_round_to_int(args, Rounding::Floor)
+}
+
+pub fn k_ceiling(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_round_to_int(args, Rounding::Ceiling)
+}
+
+pub fn k_round(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_round_to_int(args, Rounding::Round)
+}
+
+pub fn k_truncate(_: &mut Env, args: &Expr) -> Result<Expr> {
+    f_round_to_int(args, Rounding::Truncate)
+}
+
+pub fn k_float(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any));
+    Ok(kfloat(to_float(x)?))
+}
+
+// `ffloor` and friends take only a float, like Emacs
+macro_rules! def_float_rounding {
+    ($name: ident, $rounding: expr) => {
         pub fn $name(_: &mut Env, args: &Expr) -> Result<Expr> {
-            get_args!(args, (x, Int)(y, Int));
-            Ok(kbool(expr!(x $op y)))
+            get_args!(args, (x, Float));
+            Ok(kfloat($rounding.float(x)))
         }
     };
 }
 
-def_arith_cmp!(k_gt, >);
-def_arith_cmp!(k_ge, >=);
-def_arith_cmp!(k_lt, <);
-def_arith_cmp!(k_le, <=);
-def_arith_cmp!(k_eq, ==);
-def_arith_cmp!(k_neq, !=);
+def_float_rounding!(k_ffloor, Rounding::Floor);
+def_float_rounding!(k_fceiling, Rounding::Ceiling);
+def_float_rounding!(k_fround, Rounding::Round);
+def_float_rounding!(k_ftruncate, Rounding::Truncate);
 
 pub fn k_concat(env: &mut Env, args: &Expr) -> Result<Expr> {
-    let res = f_foldl(
-        env,
-        &|_, acc, x| match (acc, x) {
-            (&Expr::Str(ref acc), &Expr::Str(ref x)) => Ok(kstr(format!("{}{}", acc, x))),
-            (_, y) => Err(E::Type(Type::Str, y.clone())),
-        },
-        &kstr(""),
-        &args,
-    );
-    Ok(res?.clone())
+    let mut strings = Vec::new();
+    let mut head = args;
+    while let Expr::Cons(s, rest) = head {
+        strings.push(get_args_one!(s.deref(), Str)?);
+        head = rest.deref();
+    }
+    // the result is made at once after it is known to fit in the budget
+    let len = strings.iter().map(|s| s.len()).sum();
+    env.reserve(len)?;
+    let mut res = String::with_capacity(len);
+    for s in strings {
+        res.push_str(s);
+    }
+    Ok(kstr(res))
 }
 
 pub fn k_funcall(env: &mut Env, args: &Expr) -> Result<Expr> {
@@ -84,6 +446,40 @@ pub fn k_funcall(env: &mut Env, args: &Expr) -> Result<Expr> {
     }
 }
 
+pub fn k_throw(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (tag, Any)(value, Any));
+    Err(E::Throw(tag.clone(), value.clone()))
+}
+
+pub fn k_signal(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any)(data, Any));
+    match sym {
+        Expr::Sym(_) => Err(E::Signal(sym.clone(), data.clone())),
+        sym => Err(E::Type(Type::Sym, sym.clone())),
+    }
+}
+
+// the message is formatted like `format-message`
+pub fn k_error(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (string, objects) = print::format_args(args)?;
+    let message = print::format(env, string, &objects, true)?;
+    Err(E::Signal(Expr::Sym(symbol::ERROR), klist!(message)))
+}
+
+pub fn k_error_message_string(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (err, Cons));
+    let (sym, data) = err;
+    let message = match (sym, data) {
+        (Expr::Sym(symbol::ERROR), Expr::Cons(message, _)) => match message.deref() {
+            Expr::Str(message) => message.deref().clone(),
+            message => message.to_string(),
+        },
+        (sym, Expr::Nil) => sym.to_string(),
+        (sym, data) => format!("{}: {}", sym, data),
+    };
+    Ok(kstr(message))
+}
+
 pub fn k_cons(_: &mut Env, args: &Expr) -> Result<Expr> {
     get_args!(args, (car, Any)(cdr, Any));
     Ok(kcons(car.clone(), cdr.clone()))
@@ -101,18 +497,33 @@ pub fn k_cdr(_: &mut Env, args: &Expr) -> Result<Expr> {
 
 pub fn k_equal_p(_: &mut Env, args: &Expr) -> Result<Expr> {
     get_args!(args, (x, Any)(y, Any));
-    if x == y {
-        Ok(ksym("t"))
+    if is_equal(x, y)? {
+        Ok(Expr::Sym(symbol::T))
     } else {
         Ok(knil())
     }
 }
 
+pub fn k_eq_p(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any)(y, Any));
+    Ok(kbool(is_eq(x, y)))
+}
+
+pub fn k_eql_p(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any)(y, Any));
+    Ok(kbool(is_eql(x, y)))
+}
+
+pub fn k_equal(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (x, Any)(y, Any));
+    Ok(kbool(is_equal(x, y)?))
+}
+
 pub fn k_string_to_number(_: &mut Env, args: &Expr) -> Result<Expr> {
     get_args!(args, (s, Str));
-    match s.parse() {
+    match s.parse::<BigInt>() {
         // TODO: handle float case
-        Ok(i) => Ok(Expr::Int(i)),
+        Ok(i) => Ok(kbigint(i)),
         Err(_) => Err(E::InvalidArgument(args.clone())),
     }
 }
@@ -132,28 +543,102 @@ pub fn k_substring(_: &mut Env, args: &Expr) -> Result<Expr> {
     }
 }
 
+fn to_char(c: Kint) -> Result<char> {
+    if c < 0 {
+        return Err(E::Type(Type::Char, kint(c)));
+    }
+    match ::std::char::from_u32(c as u32) {
+        Some(c) => Ok(c),
+        None => Err(E::Type(Type::Char, kint(c))),
+    }
+}
+
+pub fn k_char_to_string(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (c, Int));
+    Ok(kstr(to_char(c)?.to_string()))
+}
+
+pub fn k_string_to_char(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (s, Str));
+    match s.chars().next() {
+        Some(c) => Ok(kint(c as Kint)),
+        None => Ok(kint(0)),
+    }
+}
+
+pub fn k_char_equal(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (c1, Int)(c2, Int));
+    if c1 == c2 {
+        return Ok(kbool(true));
+    }
+    let case_fold = match env.find("case-fold-search") {
+        Ok(v) => v != knil(),
+        Err(_) => true,
+    };
+    if !case_fold {
+        return Ok(kbool(false));
+    }
+    let c1 = to_char(c1)?.to_lowercase();
+    let c2 = to_char(c2)?.to_lowercase();
+    Ok(kbool(c1.eq(c2)))
+}
+
 pub fn init(env: &mut Env) -> Result<()> {
     env.fregister("+", kprim("k_add", k_add));
     env.fregister("-", kprim("k_sub", k_sub));
     env.fregister("/", kprim("k_div", k_div));
     env.fregister("*", kprim("k_mul", k_mul));
+    env.fregister("%", kprim("k_rem", k_rem));
+    env.fregister("mod", kprim("k_mod", k_mod));
+    env.fregister("floor", kprim("k_floor", k_floor));
+    env.fregister("ceiling", kprim("k_ceiling", k_ceiling));
+    env.fregister("round", kprim("k_round", k_round));
+    env.fregister("truncate", kprim("k_truncate", k_truncate));
+    env.fregister("float", kprim("k_float", k_float));
+    env.fregister("ffloor", kprim("k_ffloor", k_ffloor));
+    env.fregister("fceiling", kprim("k_fceiling", k_fceiling));
+    env.fregister("fround", kprim("k_fround", k_fround));
+    env.fregister("ftruncate", kprim("k_ftruncate", k_ftruncate));
     env.fregister(">", kprim("k_gt", k_gt));
     env.fregister(">=", kprim("k_ge", k_ge));
     env.fregister("<", kprim("k_lt", k_lt));
     env.fregister("<=", kprim("k_le", k_le));
     env.fregister("=", kprim("k_eq", k_eq));
     env.fregister("/=", kprim("k_neq", k_neq));
+    env.fregister("max", kprim("k_max", k_max));
+    env.fregister("min", kprim("k_min", k_min));
+    env.fregister("zerop", kprim("k_zerop", k_zerop));
+    env.fregister("plusp", kprim("k_plusp", k_plusp));
+    env.fregister("minusp", kprim("k_minusp", k_minusp));
+    env.fregister("natnump", kprim("k_natnump", k_natnump));
+    env.fregister("1+", kprim("k_add1", k_add1));
+    env.fregister("1-", kprim("k_sub1", k_sub1));
+    env.fregister("abs", kprim("k_abs", k_abs));
     env.fregister("concat", kprim("k_concat", k_concat));
     env.fregister("funcall", kprim("k_funcall", k_funcall));
+    env.fregister("throw", kprim("k_throw", k_throw));
+    env.fregister("signal", kprim("k_signal", k_signal));
+    env.fregister("error", kprim("k_error", k_error));
+    env.fregister(
+        "error-message-string",
+        kprim("k_error_message_string", k_error_message_string),
+    );
     env.fregister("cons", kprim("k_cons", k_cons));
     env.fregister("car", kprim("k_car", k_car));
     env.fregister("cdr", kprim("k_cdr", k_cdr));
     env.fregister("equalp", kprim("k_equal_p", k_equal_p));
+    env.fregister("eq", kprim("k_eq_p", k_eq_p));
+    env.fregister("eql", kprim("k_eql_p", k_eql_p));
+    env.fregister("equal", kprim("k_equal", k_equal));
     env.fregister(
         "string-to-number",
         kprim("k_string_to_number", k_string_to_number),
     );
     env.fregister("substring", kprim("k_substring", k_substring));
+    env.fregister("char-to-string", kprim("k_char_to_string", k_char_to_string));
+    env.fregister("string-to-char", kprim("k_string_to_char", k_string_to_char));
+    env.fregister("char-equal", kprim("k_char_equal", k_char_equal));
+    env.make_special("case-fold-search");
     env.register("t", ksym("t"));
     Ok(())
 }
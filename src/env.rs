@@ -1,72 +1,563 @@
-use std::collections::HashMap;
-use std::collections::LinkedList;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use expr::{Error as E, Expr, Proc, Result};
+use expr::{Error as E, Expr, Kint, Limit, Proc, Result};
+use hash::is_eq;
+use symbol::{Symbol, SymbolMap, SymbolSet};
+use util::*;
+
+/// a frame of lexical bindings. Closures share frames with the scope that created them.
+pub struct Frame {
+    vars: RefCell<SymbolMap<Expr>>,
+    funcs: RefCell<SymbolMap<Proc>>,
+    parent: Option<Rc<Frame>>,
+}
+
+impl Frame {
+    fn new(parent: Option<Rc<Frame>>) -> Frame {
+        Frame {
+            vars: RefCell::new(SymbolMap::default()),
+            funcs: RefCell::new(SymbolMap::default()),
+            parent,
+        }
+    }
+}
+
+// whether `proc` is a closure over `frame`
+fn closes_over(proc: &Proc, frame: &Rc<Frame>) -> bool {
+    match proc {
+        Proc::Lambda(_, _, Some(captured)) => Rc::ptr_eq(captured, frame),
+        _ => false,
+    }
+}
+
+// drops a frame that is left. The closures that `labels` or `setq` store in the frame itself
+// make reference cycles, which are broken if nothing else refers to the frame at this point.
+fn release(frame: Option<Rc<Frame>>) {
+    let mut frame = frame;
+    while let Some(current) = frame {
+        let self_refs = {
+            let funcs = current.funcs.borrow();
+            let vars = current.vars.borrow();
+            funcs.values().filter(|f| closes_over(f, &current)).count()
+                + vars
+                    .values()
+                    .filter(|v| match v {
+                        Expr::Proc(f) => closes_over(f, &current),
+                        _ => false,
+                    })
+                    .count()
+        };
+        if Rc::strong_count(&current) != self_refs + 1 {
+            return;
+        }
+        if self_refs != 0 {
+            // moved out so that the closures are dropped after the borrows end
+            let funcs = current.funcs.replace(SymbolMap::default());
+            let vars = current.vars.replace(SymbolMap::default());
+            drop((funcs, vars));
+        }
+        // the parent may be kept only by its own closures too
+        frame = match Rc::try_unwrap(current) {
+            Ok(current) => current.parent,
+            Err(_) => return,
+        };
+    }
+}
+
+// iterates a lexical environment from the innermost frame
+fn frames(lexical: &Option<Rc<Frame>>) -> impl Iterator<Item = &Frame> {
+    let mut frame = lexical.as_deref();
+    ::std::iter::from_fn(move || {
+        let current = frame?;
+        frame = current.parent.as_deref();
+        Some(current)
+    })
+}
+
+// a local frame on the stack
+struct Scope {
+    // bindings of special variables
+    dynamic: SymbolMap<Expr>,
+    // the lexical environment to restore when the frame ends
+    saved: Option<Rc<Frame>>,
+}
+
+/// the default limit of nested evaluations, like `max-lisp-eval-depth` of Emacs
+pub const DEFAULT_MAX_EVAL_DEPTH: usize = 1600;
+/// the default limit of the Rust stack used by nested evaluations.
+/// It fits in the 2MiB stack of threads spawned by `std::thread`.
+pub const DEFAULT_MAX_STACK_SIZE: usize = 1 << 20;
+
+// the address of a local variable of the caller, approximately
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
+
+/// limits of a single evaluation. `None` means unlimited.
+/// Running out of a limit cannot be caught by `condition-case`, and it skips the cleanup forms of
+/// `unwind-protect`, which would run out of it too.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// evaluation steps, roughly the number of forms evaluated
+    pub max_steps: Option<u64>,
+    /// nesting of evaluations that are not tail calls
+    pub max_depth: Option<usize>,
+    /// approximate bytes of Lisp objects made
+    pub max_allocation: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+// the deadline is checked once in this many steps, as reading the clock is slow
+const CHECK_INTERVAL: u64 = 256;
+
+// the remaining resources of the current evaluation
+struct Budget {
+    limits: Limits,
+    steps: u64,
+    base_depth: usize,
+    base_allocation: usize,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    fn allocation_left(&self) -> Option<usize> {
+        let used = allocated().wrapping_sub(self.base_allocation);
+        self.limits
+            .max_allocation
+            .map(|max| max.saturating_sub(used))
+    }
+}
+
+/// interrupts evaluations in an `Env` from another thread
+#[derive(Clone, Debug)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// makes the running (or the next) evaluation fail with `Limit::Interrupted`.
+    /// The interrupt lasts until the outermost evaluation or `Env::with_limits` returns.
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
 
 pub struct Env {
-    global: HashMap<String, Expr>,
-    local: LinkedList<HashMap<String, Expr>>,
-    fglobal: HashMap<String, Proc>,
-    flocal: LinkedList<HashMap<String, Proc>>,
+    global: SymbolMap<Expr>,
+    fglobal: SymbolMap<Proc>,
+    specials: SymbolSet,
+    lexical_binding: bool,
+    lexical: Option<Rc<Frame>>,
+    stack: Vec<Scope>,
+    eval_depth: usize,
+    max_eval_depth: usize,
+    // the stack address of the outermost evaluation
+    stack_base: usize,
+    max_stack_size: usize,
+    budget: Option<Budget>,
+    interrupted: Arc<AtomicBool>,
+    // names whose global value and function cannot be changed by Lisp code
+    protected: SymbolSet,
+    // property lists of symbols as pairs of a property and its value
+    plists: SymbolMap<Vec<(Expr, Expr)>>,
 }
 
 impl Env {
     pub fn new() -> Env {
         Env {
-            global: HashMap::new(),
-            local: LinkedList::new(),
-            fglobal: HashMap::new(),
-            flocal: LinkedList::new(),
+            global: SymbolMap::default(),
+            fglobal: SymbolMap::default(),
+            specials: SymbolSet::default(),
+            lexical_binding: true,
+            lexical: None,
+            stack: Vec::new(),
+            eval_depth: 0,
+            max_eval_depth: DEFAULT_MAX_EVAL_DEPTH,
+            stack_base: 0,
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            budget: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            protected: SymbolSet::default(),
+            plists: SymbolMap::default(),
         }
     }
 
+    /// switches between lexical (the default) and dynamic binding of non-special variables
+    pub fn set_lexical_binding(&mut self, lexical: bool) {
+        self.lexical_binding = lexical;
+    }
+
+    pub fn lexical_binding(&self) -> bool {
+        self.lexical_binding
+    }
+
+    /// declares `name` as a special (dynamically bound) variable like `defvar`
+    pub fn make_special<S: Into<Symbol>>(&mut self, name: S) {
+        self.specials.insert(name.into());
+    }
+
+    pub fn is_special<S: Into<Symbol>>(&self, name: S) -> bool {
+        !self.lexical_binding || self.specials.contains(&name.into())
+    }
+
+    /// the current lexical environment, captured by closures
+    pub fn capture(&self) -> Option<Rc<Frame>> {
+        self.lexical.clone()
+    }
+
     pub fn new_local(&mut self) {
-        self.local.push_front(HashMap::new());
-        self.flocal.push_front(HashMap::new());
+        let parent = self.lexical.clone();
+        self.new_closure_local(parent);
+    }
+
+    /// starts a local frame whose lexical parent is `captured` instead of the current one
+    pub fn new_closure_local(&mut self, captured: Option<Rc<Frame>>) {
+        let saved = self.lexical.take();
+        self.stack.push(Scope {
+            dynamic: SymbolMap::default(),
+            saved,
+        });
+        self.lexical = Some(Rc::new(Frame::new(captured)));
     }
 
     pub fn end_local(&mut self) {
-        self.local.pop_front();
-        self.flocal.pop_front();
+        if let Some(scope) = self.stack.pop() {
+            let frame = ::std::mem::replace(&mut self.lexical, scope.saved);
+            release(frame);
+        }
+    }
+
+    /// limits the nesting of evaluations that are not tail calls
+    pub fn set_max_eval_depth(&mut self, depth: usize) {
+        self.max_eval_depth = depth;
+    }
+
+    pub fn max_eval_depth(&self) -> usize {
+        self.max_eval_depth
+    }
+
+    /// limits the bytes of the Rust stack used by nested evaluations
+    pub fn set_max_stack_size(&mut self, bytes: usize) {
+        self.max_stack_size = bytes;
+    }
+
+    pub fn max_stack_size(&self) -> usize {
+        self.max_stack_size
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupted.clone())
+    }
+
+    /// runs `f` with `limits` applied to the evaluations in it
+    pub fn with_limits<F, T>(&mut self, limits: &Limits, f: F) -> T
+    where
+        F: FnOnce(&mut Env) -> T,
+    {
+        let budget = Budget {
+            limits: limits.clone(),
+            steps: 0,
+            base_depth: self.eval_depth,
+            base_allocation: allocated(),
+            deadline: limits.timeout.map(|t| Instant::now() + t),
+        };
+        let outer = self.budget.replace(budget);
+        let ret = f(self);
+        self.budget = outer;
+        self.interrupted.store(false, Ordering::Relaxed);
+        ret
     }
 
-    pub fn register<S: Into<String>>(&mut self, name: S, value: Expr) {
-        match self.local.front_mut() {
-            Some(l) => l.insert(name.into(), value),
-            None => self.global.insert(name.into(), value),
+    /// counts an evaluation step. Fails if the budget has run out or the evaluation is interrupted.
+    pub fn step(&mut self) -> Result<()> {
+        // the flag stays so that no Lisp code runs until the evaluation is over
+        if self.interrupted.load(Ordering::Relaxed) {
+            return Err(E::Limit(Limit::Interrupted));
+        }
+        let budget = match self.budget {
+            Some(ref mut budget) => budget,
+            None => return Ok(()),
         };
+        budget.steps += 1;
+        if let Some(max) = budget.limits.max_steps {
+            if budget.steps > max {
+                return Err(E::Limit(Limit::Steps));
+            }
+        }
+        if budget.allocation_left() == Some(0) {
+            return Err(E::Limit(Limit::Allocation));
+        }
+        if budget.steps % CHECK_INTERVAL == 0 {
+            if let Some(deadline) = budget.deadline {
+                if Instant::now() >= deadline {
+                    return Err(E::Limit(Limit::Timeout));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// fails if making `bytes` of Lisp objects would exceed the allocation budget
+    pub fn reserve(&self, bytes: usize) -> Result<()> {
+        match self.budget.as_ref().and_then(|b| b.allocation_left()) {
+            Some(left) if bytes > left => Err(E::Limit(Limit::Allocation)),
+            _ => Ok(()),
+        }
+    }
+
+    /// counts a nested evaluation. Fails with `excessive-lisp-nesting` over the limits.
+    pub fn enter_eval(&mut self) -> Result<()> {
+        let here = stack_address();
+        if self.eval_depth == 0 {
+            self.stack_base = here;
+        }
+        let used = here.abs_diff(self.stack_base);
+        if let Some(ref budget) = self.budget {
+            if let Some(max) = budget.limits.max_depth {
+                if self.eval_depth - budget.base_depth >= max {
+                    return Err(E::Limit(Limit::Depth));
+                }
+            }
+        }
+        if self.eval_depth >= self.max_eval_depth || used > self.max_stack_size {
+            return Err(E::Signal(
+                ksym("excessive-lisp-nesting"),
+                klist!(kint(self.eval_depth as Kint)),
+            ));
+        }
+        self.eval_depth += 1;
+        Ok(())
+    }
+
+    pub fn leave_eval(&mut self) {
+        self.eval_depth -= 1;
+        if self.eval_depth == 0 {
+            self.interrupted.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// the number of local frames
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// whether the local frames above `depth` bind special variables
+    pub fn has_dynamic_bindings(&self, depth: usize) -> bool {
+        self.stack[depth..].iter().any(|scope| !scope.dynamic.is_empty())
+    }
+
+    /// removes the local frames above `depth`
+    pub fn unwind_to(&mut self, depth: usize) {
+        while self.stack.len() > depth {
+            self.end_local();
+        }
+    }
+
+    /// binds `name` in the innermost frame, dynamically if it is special
+    pub fn register<S: Into<Symbol>>(&mut self, name: S, value: Expr) {
+        let name = name.into();
+        if self.is_special(&name) {
+            match self.stack.last_mut() {
+                Some(scope) => scope.dynamic.insert(name, value),
+                None => self.global.insert(name, value),
+            };
+        } else {
+            match self.lexical {
+                Some(ref frame) => frame.vars.borrow_mut().insert(name, value),
+                None => self.global.insert(name, value),
+            };
+        }
+    }
+
+    /// protects the global value and the function of `name` from `set`, `fset` and the like.
+    /// Local bindings of `name` are still allowed.
+    pub fn protect<S: Into<Symbol>>(&mut self, name: S) {
+        self.protected.insert(name.into());
+    }
+
+    /// protects all the global variables and functions defined so far
+    pub fn protect_globals(&mut self) {
+        let names: Vec<Symbol> = self
+            .global
+            .keys()
+            .chain(self.fglobal.keys())
+            .cloned()
+            .collect();
+        self.protected.extend(names);
+    }
+
+    pub fn is_protected<S: Into<Symbol>>(&self, name: S) -> bool {
+        self.protected.contains(&name.into())
+    }
+
+    /// signals `setting-constant` if `name` is protected
+    pub fn check_protected(&self, name: &Symbol) -> Result<()> {
+        if self.is_protected(name) {
+            Err(E::Signal(
+                ksym("setting-constant"),
+                klist!(Expr::Sym(name.clone())),
+            ))
+        } else {
+            Ok(())
+        }
     }
 
-    pub fn fregister<S: Into<String>>(&mut self, name: S, value: Proc) {
-        match self.flocal.front_mut() {
-            Some(l) => l.insert(name.into(), value),
+    /// removes the global functions for which `keep` returns false
+    pub fn retain_functions<F>(&mut self, keep: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.fglobal.retain(|name, _| keep(&name.name()));
+    }
+
+    /// updates the innermost binding of `name`. Binds globally if `name` is not bound.
+    /// Fails if the global value of a protected name would change.
+    pub fn set<S: Into<Symbol>>(&mut self, name: S, value: Expr) -> Result<()> {
+        let name = name.into();
+        if self.is_special(&name) {
+            for scope in self.stack.iter_mut().rev() {
+                if let Some(v) = scope.dynamic.get_mut(&name) {
+                    *v = value;
+                    return Ok(());
+                }
+            }
+        } else {
+            for frame in frames(&self.lexical) {
+                if let Some(v) = frame.vars.borrow_mut().get_mut(&name) {
+                    *v = value;
+                    return Ok(());
+                }
+            }
+        }
+        self.set_global(name, value)
+    }
+
+    /// sets the global value of `name`, ignoring local bindings
+    pub fn set_global<S: Into<Symbol>>(&mut self, name: S, value: Expr) -> Result<()> {
+        let name = name.into();
+        self.check_protected(&name)?;
+        self.global.insert(name, value);
+        Ok(())
+    }
+
+    pub fn is_global_bound<S: Into<Symbol>>(&self, name: S) -> bool {
+        self.global.contains_key(&name.into())
+    }
+
+    pub fn fregister<S: Into<Symbol>>(&mut self, name: S, value: Proc) {
+        match self.lexical {
+            Some(ref frame) => frame.funcs.borrow_mut().insert(name.into(), value),
             None => self.fglobal.insert(name.into(), value),
         };
     }
 
-    pub fn find(&self, name: &String) -> Result<&Expr> {
-        for m in self.local.iter() {
-            match m.get(name) {
-                Some(v) => return Ok(v),
-                None => (),
+    /// sets the global function definition of `name` like `fset`
+    pub fn fset<S: Into<Symbol>>(&mut self, name: S, value: Proc) -> Result<()> {
+        let name = name.into();
+        self.check_protected(&name)?;
+        self.fglobal.insert(name, value);
+        Ok(())
+    }
+
+    pub fn find<S: Into<Symbol>>(&self, name: S) -> Result<Expr> {
+        let name = &name.into();
+        if self.is_special(name) {
+            for scope in self.stack.iter().rev() {
+                if let Some(v) = scope.dynamic.get(name) {
+                    return Ok(v.clone());
+                }
+            }
+        } else {
+            for frame in frames(&self.lexical) {
+                if let Some(v) = frame.vars.borrow().get(name) {
+                    return Ok(v.clone());
+                }
             }
         }
         match self.global.get(name) {
-            Some(v) => Ok(v),
-            None => Err(E::Unbound(name.clone())),
+            Some(v) => Ok(v.clone()),
+            None => Err(E::Unbound(name.name().to_string())),
         }
     }
 
-    pub fn ffind(&self, name: &String) -> Result<&Proc> {
-        for m in self.flocal.iter() {
-            match m.get(name) {
-                Some(v) => return Ok(v),
-                None => (),
+    pub fn ffind<S: Into<Symbol>>(&self, name: S) -> Result<Proc> {
+        let name = &name.into();
+        for frame in frames(&self.lexical) {
+            if let Some(v) = frame.funcs.borrow().get(name) {
+                return Ok(v.clone());
             }
         }
         match self.fglobal.get(name) {
-            Some(v) => Ok(v),
-            None => Err(E::Unbound(name.clone())),
+            Some(v) => Ok(v.clone()),
+            None => Err(E::Unbound(name.name().to_string())),
+        }
+    }
+
+    /// removes the global value of `name` like `makunbound`
+    pub fn makunbound<S: Into<Symbol>>(&mut self, name: S) -> Result<()> {
+        let name = name.into();
+        self.check_protected(&name)?;
+        self.global.remove(&name);
+        Ok(())
+    }
+
+    /// removes the global function definition of `name` like `fmakunbound`
+    pub fn fmakunbound<S: Into<Symbol>>(&mut self, name: S) -> Result<()> {
+        let name = name.into();
+        self.check_protected(&name)?;
+        self.fglobal.remove(&name);
+        Ok(())
+    }
+
+    /// finds the dynamic or global value of `name`. Lexical bindings are not visible,
+    /// as with `symbol-value`.
+    pub fn find_dynamic<S: Into<Symbol>>(&self, name: S) -> Result<Expr> {
+        let name = &name.into();
+        for scope in self.stack.iter().rev() {
+            if let Some(v) = scope.dynamic.get(name) {
+                return Ok(v.clone());
+            }
+        }
+        match self.global.get(name) {
+            Some(v) => Ok(v.clone()),
+            None => Err(E::Unbound(name.name().to_string())),
         }
     }
+
+    /// the value of the property `prop` of `name`, or nil
+    pub fn get<S: Into<Symbol>>(&self, name: S, prop: &Expr) -> Expr {
+        self.plists
+            .get(&name.into())
+            .and_then(|plist| plist.iter().find(|(p, _)| is_eq(p, prop)))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(knil)
+    }
+
+    /// sets the property `prop` of `name` to `value`, adding it to the end of the plist if new.
+    /// Fails if `name` is protected.
+    pub fn put<S: Into<Symbol>>(&mut self, name: S, prop: Expr, value: Expr) -> Result<()> {
+        let name = name.into();
+        self.check_protected(&name)?;
+        let plist = self.plists.entry(name).or_default();
+        match plist.iter_mut().find(|(p, _)| is_eq(p, &prop)) {
+            Some(entry) => entry.1 = value,
+            None => plist.push((prop, value)),
+        }
+        Ok(())
+    }
+
+    /// the property list of `name` as a Lisp list
+    pub fn plist<S: Into<Symbol>>(&self, name: S) -> Expr {
+        let plist = match self.plists.get(&name.into()) {
+            Some(plist) => plist,
+            None => return knil(),
+        };
+        plist.iter().rev().fold(knil(), |acc, (prop, value)| {
+            kcons(prop.clone(), kcons(value.clone(), acc))
+        })
+    }
 }
@@ -1,13 +1,16 @@
 use std::ops::Deref;
 use std::rc::Rc;
 
-use env::Env;
+use env::{Env, Limits};
 use expr::{Error as E, Expr, Proc, Result, Type};
+use hash::is_eq;
+use symbol;
+use symbol::Symbol;
 use util::*;
 
 fn bind_name(env: &mut Env, name: &Expr, value: Expr) -> Result<()> {
     match name {
-        &Expr::Sym(ref name) => Ok(env.register(name.deref().clone(), value)),
+        Expr::Sym(name) => Ok(env.register(name, value)),
         name => return Err(E::Form(name.clone())),
     }
 }
@@ -16,8 +19,8 @@ fn bind_names(env: &mut Env, params: &Expr, args: &Expr) -> Result<()> {
     let mut phead = params;
     let mut ahead = args;
     let mut in_optional = false;
-    let optional = ksym("&optional");
-    let rest = ksym("&rest");
+    let optional = Expr::Sym(symbol::OPTIONAL);
+    let rest = Expr::Sym(symbol::REST);
     let nil = &knil();
     while phead != nil || ahead != nil {
         match phead {
@@ -63,18 +66,58 @@ fn bind_names(env: &mut Env, params: &Expr, args: &Expr) -> Result<()> {
     Ok(())
 }
 
-pub fn funcall(env: &mut Env, f: &Proc, args: &Expr) -> Result<Expr> {
-    match f {
-        Proc::Prim(_, f) => f(env, args),
-        Proc::Lambda(params, body) => {
-            env.new_local();
-            bind_names(env, params.deref(), args)?;
-            let ret = eval(env, body.deref());
-            env.end_local();
-            ret
+// runs `f` in a new local frame. The frame is removed even if `f` fails.
+fn with_local<F>(env: &mut Env, f: F) -> Result<Expr>
+where
+    F: FnOnce(&mut Env) -> Result<Expr>,
+{
+    env.new_local();
+    let ret = f(env);
+    env.end_local();
+    ret
+}
+
+// the rest of an evaluation step. Forms in tail position are returned instead of being
+// evaluated so that `trampoline` can run them without growing the Rust stack.
+enum Tail {
+    Done(Expr),
+    Eval(Expr),
+    Call(Proc, Expr),
+}
+
+// runs `next` to the end. Local frames pushed on the way are removed when it finishes.
+fn trampoline(env: &mut Env, mut next: Tail) -> Result<Expr> {
+    env.enter_eval()?;
+    let depth = env.depth();
+    let ret = loop {
+        let step = match next {
+            Tail::Done(value) => break Ok(value),
+            Tail::Eval(expr) => env.step().and_then(|()| eval_step(env, &expr)),
+            Tail::Call(Proc::Lambda(params, body, captured), args) => {
+                // a tail call doesn't need the frames of the caller,
+                // unless they hold dynamic bindings the callee may see
+                if !env.has_dynamic_bindings(depth) {
+                    env.unwind_to(depth);
+                }
+                // the body sees the environment where the lambda was made, not the caller's
+                env.new_closure_local(captured);
+                bind_names(env, &params, &args).map(|()| Tail::Eval(body.deref().clone()))
+            }
+            Tail::Call(Proc::Prim(_, f), args) => f(env, &args).map(Tail::Done),
+            Tail::Call(f, _) => Err(E::NotFunction(kproc(f))),
+        };
+        match step {
+            Ok(step) => next = step,
+            Err(e) => break Err(e),
         }
-        f => Err(E::NotFunction(kproc(f.clone()))),
-    }
+    };
+    env.unwind_to(depth);
+    env.leave_eval();
+    ret
+}
+
+pub fn funcall(env: &mut Env, f: &Proc, args: &Expr) -> Result<Expr> {
+    trampoline(env, Tail::Call(f.clone(), args.clone()))
 }
 
 fn k_quote(_: &mut Env, args: &Expr) -> Result<Expr> {
@@ -82,11 +125,127 @@ fn k_quote(_: &mut Env, args: &Expr) -> Result<Expr> {
     Ok(sexp.clone())
 }
 
-fn f_lambda(_: &mut Env, args: &Expr) -> Result<Proc> {
+// returns the argument of `(name x)`
+fn quoted_form(name: Symbol, expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Cons(car, cdr) => match (car.deref(), cdr.deref()) {
+            (Expr::Sym(sym), Expr::Cons(x, nil)) if *sym == name && nil.deref() == &knil() => {
+                Some(x.deref())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn f_backquote_list(env: &mut Env, list: &Expr, level: usize) -> Result<Expr> {
+    let mut elements = Vec::new();
+    let mut head = list;
+    let mut tail = knil();
+    loop {
+        // `(a . ,b)` is read as `(a \, b)`
+        if quoted_form(symbol::COMMA, head).is_some()
+            || quoted_form(symbol::COMMA_AT, head).is_some()
+        {
+            tail = f_backquote(env, head, level)?;
+            break;
+        }
+        match head {
+            Expr::Cons(car, cdr) => {
+                match quoted_form(symbol::COMMA_AT, car) {
+                    Some(x) if level == 0 => {
+                        let spliced = eval(env, x)?;
+                        let mut shead = &spliced;
+                        while let Expr::Cons(scar, scdr) = shead {
+                            elements.push(scar.deref().clone());
+                            shead = scdr.deref();
+                        }
+                        match shead {
+                            Expr::Nil => (),
+                            // a dotted list can be spliced only at the end
+                            shead if cdr.deref() == &knil() => tail = shead.clone(),
+                            shead => return Err(E::Type(Type::Cons, shead.clone())),
+                        }
+                    }
+                    Some(x) => elements.push(klist!(
+                        Expr::Sym(symbol::COMMA_AT),
+                        f_backquote(env, x, level - 1)?
+                    )),
+                    None => elements.push(f_backquote(env, car, level)?),
+                }
+                head = cdr.deref();
+            }
+            Expr::Nil => break,
+            atom => {
+                tail = atom.clone();
+                break;
+            }
+        }
+    }
+    Ok(elements
+        .into_iter()
+        .rev()
+        .fold(tail, |acc, e| kcons(e, acc)))
+}
+
+// expands backquoted `template`. `level` is the depth of nested backquotes.
+fn f_backquote(env: &mut Env, template: &Expr, level: usize) -> Result<Expr> {
+    if let Some(x) = quoted_form(symbol::COMMA, template) {
+        return match level {
+            0 => eval(env, x),
+            _ => Ok(klist!(
+                Expr::Sym(symbol::COMMA),
+                f_backquote(env, x, level - 1)?
+            )),
+        };
+    }
+    if let Some(x) = quoted_form(symbol::COMMA_AT, template) {
+        return match level {
+            0 => Err(E::Form(template.clone())),
+            _ => Ok(klist!(
+                Expr::Sym(symbol::COMMA_AT),
+                f_backquote(env, x, level - 1)?
+            )),
+        };
+    }
+    if let Some(x) = quoted_form(symbol::BACKQUOTE, template) {
+        return Ok(klist!(
+            Expr::Sym(symbol::BACKQUOTE),
+            f_backquote(env, x, level + 1)?
+        ));
+    }
+    match template {
+        Expr::Cons(_, _) => f_backquote_list(env, template, level),
+        Expr::Vector(v) => {
+            let list = v
+                .borrow()
+                .iter()
+                .rev()
+                .fold(knil(), |acc, e| kcons(e.clone(), acc));
+            let mut elements = Vec::new();
+            let list = f_backquote_list(env, &list, level)?;
+            let mut head = &list;
+            while let Expr::Cons(car, cdr) = head {
+                elements.push(car.deref().clone());
+                head = cdr.deref();
+            }
+            Ok(kvector(elements))
+        }
+        atom => Ok(atom.clone()),
+    }
+}
+
+fn k_backquote(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (template, Any));
+    f_backquote(env, template, 0)
+}
+
+fn f_lambda(env: &mut Env, args: &Expr) -> Result<Proc> {
     match args {
-        Expr::Cons(params, body) => Ok(klambda(
-            params.deref().clone(),
-            kcons(ksym("progn"), body.deref().clone()),
+        Expr::Cons(params, body) => Ok(Proc::Lambda(
+            params.clone(),
+            Rc::new(kcons(Expr::Sym(symbol::PROGN), body.deref().clone())),
+            env.capture(),
         )),
         _ => unreachable!(),
     }
@@ -103,20 +262,128 @@ fn k_feval(env: &mut Env, args: &Expr) -> Result<Expr> {
     }
 }
 
-fn k_progn(env: &mut Env, args: &Expr) -> Result<Expr> {
+fn k_progn(env: &mut Env, args: &Expr) -> Result<Tail> {
     let mut head = args;
-    let nil = &knil();
-    let mut res = knil();
-    while head != nil {
-        match head {
-            Expr::Cons(car, cdr) => {
-                res = eval(env, car.deref())?;
-                head = cdr.deref();
+    while let Expr::Cons(car, cdr) = head {
+        match cdr.deref() {
+            Expr::Nil => return Ok(Tail::Eval(car.deref().clone())),
+            cdr => {
+                eval(env, car.deref())?;
+                head = cdr;
             }
-            _ => return Err(E::Form(args.clone())),
         }
     }
-    Ok(res)
+    match head {
+        Expr::Nil => Ok(Tail::Done(knil())),
+        _ => Err(E::Form(args.clone())),
+    }
+}
+
+// evaluates `body` in order, returning the value of the last form
+fn f_progn(env: &mut Env, body: &Expr) -> Result<Expr> {
+    let next = k_progn(env, body)?;
+    trampoline(env, next)
+}
+
+// splits a `let` binding `x`, `(x)` or `(x value)`
+fn let_binding(binding: &Expr) -> Result<(&Expr, Option<&Expr>)> {
+    match binding {
+        Expr::Sym(_) => Ok((binding, None)),
+        Expr::Cons(name, rest) => match rest.deref() {
+            Expr::Nil => Ok((name.deref(), None)),
+            Expr::Cons(value, nil) if nil.deref() == &knil() => Ok((name.deref(), Some(value))),
+            _ => Err(E::Form(binding.clone())),
+        },
+        _ => Err(E::Form(binding.clone())),
+    }
+}
+
+// the frames pushed by `let` and friends are removed by `trampoline` after the body
+fn k_let(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let (bindings, body) = match args {
+        Expr::Cons(bindings, body) => (bindings.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    // the initial values are evaluated before any variable is bound
+    let mut values = Vec::new();
+    let mut head = bindings;
+    while let Expr::Cons(binding, rest) = head {
+        let (name, value) = let_binding(binding)?;
+        let value = match value {
+            Some(value) => eval(env, value)?,
+            None => knil(),
+        };
+        values.push((name, value));
+        head = rest.deref();
+    }
+    env.new_local();
+    for (name, value) in values {
+        bind_name(env, name, value)?;
+    }
+    k_progn(env, body)
+}
+
+fn k_let_star(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let (bindings, body) = match args {
+        Expr::Cons(bindings, body) => (bindings.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    env.new_local();
+    let mut head = bindings;
+    while let Expr::Cons(binding, rest) = head {
+        let (name, value) = let_binding(binding)?;
+        let value = match value {
+            Some(value) => eval(env, value)?,
+            None => knil(),
+        };
+        bind_name(env, name, value)?;
+        head = rest.deref();
+    }
+    k_progn(env, body)
+}
+
+// splits a function binding `(name params body...)` of `flet` and `labels`
+fn f_function_binding(env: &mut Env, binding: &Expr) -> Result<(Symbol, Proc)> {
+    match binding {
+        Expr::Cons(name, lambda) => match name.deref() {
+            Expr::Sym(name) => Ok((name.clone(), f_lambda(env, lambda.deref())?)),
+            _ => Err(E::Form(binding.clone())),
+        },
+        _ => Err(E::Form(binding.clone())),
+    }
+}
+
+fn k_flet(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let (bindings, body) = match args {
+        Expr::Cons(bindings, body) => (bindings.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    let mut functions = Vec::new();
+    let mut head = bindings;
+    while let Expr::Cons(binding, rest) = head {
+        functions.push(f_function_binding(env, binding)?);
+        head = rest.deref();
+    }
+    env.new_local();
+    for (name, f) in functions {
+        env.fregister(name, f);
+    }
+    k_progn(env, body)
+}
+
+fn k_labels(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let (bindings, body) = match args {
+        Expr::Cons(bindings, body) => (bindings.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    env.new_local();
+    let mut head = bindings;
+    while let Expr::Cons(binding, rest) = head {
+        let (name, f) = f_function_binding(env, binding)?;
+        env.fregister(name, f);
+        head = rest.deref();
+    }
+    k_progn(env, body)
 }
 
 fn k_fset(env: &mut Env, args: &Expr) -> Result<Expr> {
@@ -125,7 +392,7 @@ fn k_fset(env: &mut Env, args: &Expr) -> Result<Expr> {
     let f = feval(env, f)?;
     let tmp = klist!(s);
     get_args!(&tmp, (s, Sym));
-    env.fregister(s.deref().clone(), f.clone());
+    env.fset(s, f.clone())?;
     return Ok(knil());
 }
 
@@ -135,37 +402,248 @@ fn k_set(env: &mut Env, args: &Expr) -> Result<Expr> {
     let e = eval(env, e)?;
     let tmp = klist!(s);
     get_args!(&tmp, (s, Sym));
-    env.register(s.deref().clone(), e.clone());
+    env.set(s, e.clone())?;
     return Ok(knil());
 }
 
-fn k_if(env: &mut Env, args: &Expr) -> Result<Expr> {
+// splits `(defvar name [value [doc]])` and `(defconst name value [doc])`
+fn f_definition(args: &Expr) -> Result<(Symbol, Option<&Expr>)> {
+    let (name, rest) = match args {
+        Expr::Cons(name, rest) => match name.deref() {
+            Expr::Sym(name) => (name.clone(), rest.deref()),
+            name => return Err(E::Type(Type::Sym, name.clone())),
+        },
+        _ => return Err(E::Form(args.clone())),
+    };
+    match rest {
+        Expr::Nil => Ok((name, None)),
+        Expr::Cons(value, doc) => match doc.deref() {
+            Expr::Nil | Expr::Cons(_, _) => Ok((name, Some(value.deref()))),
+            _ => Err(E::Form(args.clone())),
+        },
+        _ => Err(E::Form(args.clone())),
+    }
+}
+
+fn k_defvar(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (name, value) = f_definition(args)?;
+    // a protected variable must not turn special either
+    env.check_protected(&name)?;
+    env.make_special(&name);
+    // the initial value is set only if the variable is not bound yet
+    if let Some(value) = value {
+        if !env.is_global_bound(&name) {
+            let value = eval(env, value)?;
+            env.set_global(&name, value)?;
+        }
+    }
+    Ok(Expr::Sym(name))
+}
+
+fn k_defconst(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (name, value) = f_definition(args)?;
+    env.check_protected(&name)?;
+    let value = match value {
+        Some(value) => eval(env, value)?,
+        None => return Err(E::ArityShort),
+    };
+    env.make_special(&name);
+    env.set_global(&name, value)?;
+    Ok(Expr::Sym(name))
+}
+
+fn k_if(env: &mut Env, args: &Expr) -> Result<Tail> {
     // TODO: optional else clasue. Need optional argments.
     get_args!(args, (cnd, Any)(thn, Any) & optional(els, Any));
     let res = eval(env, cnd)?;
     if res != knil() {
-        eval(env, thn)
+        Ok(Tail::Eval(thn.clone()))
     } else {
         match els {
-            Some(els) => eval(env, els),
-            None => Ok(knil()),
+            Some(els) => Ok(Tail::Eval(els.clone())),
+            None => Ok(Tail::Done(knil())),
+        }
+    }
+}
+
+fn k_cond(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let mut head = args;
+    while let Expr::Cons(clause, rest) = head {
+        let (cnd, body) = match clause.deref() {
+            Expr::Cons(cnd, body) => (cnd.deref(), body.deref()),
+            clause => return Err(E::Form(clause.clone())),
+        };
+        let res = eval(env, cnd)?;
+        if res != knil() {
+            // a clause without body returns the value of the condition
+            return match body {
+                Expr::Nil => Ok(Tail::Done(res)),
+                body => k_progn(env, body),
+            };
+        }
+        head = rest.deref();
+    }
+    Ok(Tail::Done(knil()))
+}
+
+fn k_and(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let mut head = args;
+    while let Expr::Cons(car, cdr) = head {
+        if cdr.deref() == &knil() {
+            return Ok(Tail::Eval(car.deref().clone()));
+        }
+        if eval(env, car.deref())? == knil() {
+            return Ok(Tail::Done(knil()));
+        }
+        head = cdr.deref();
+    }
+    Ok(Tail::Done(Expr::Sym(symbol::T)))
+}
+
+fn k_or(env: &mut Env, args: &Expr) -> Result<Tail> {
+    let mut head = args;
+    while let Expr::Cons(car, cdr) = head {
+        if cdr.deref() == &knil() {
+            return Ok(Tail::Eval(car.deref().clone()));
+        }
+        let res = eval(env, car.deref())?;
+        if res != knil() {
+            return Ok(Tail::Done(res));
+        }
+        head = cdr.deref();
+    }
+    Ok(Tail::Done(knil()))
+}
+
+fn k_while(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (cnd, body) = match args {
+        Expr::Cons(cnd, body) => (cnd.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    while eval(env, cnd)? != knil() {
+        f_progn(env, body)?;
+    }
+    Ok(knil())
+}
+
+fn k_catch(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (tag, body) = match args {
+        Expr::Cons(tag, body) => (tag.deref(), body.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    let tag = eval(env, tag)?;
+    let depth = env.depth();
+    match f_progn(env, body) {
+        Err(E::Throw(ref thrown, ref value)) if is_eq(thrown, &tag) => {
+            env.unwind_to(depth);
+            Ok(value.clone())
+        }
+        ret => ret,
+    }
+}
+
+// whether a handler for `conditions` (a symbol or a list of symbols) handles `sym`.
+// Every error symbol is a subtype of `error`.
+fn handles(conditions: &Expr, sym: &Expr) -> bool {
+    match conditions {
+        Expr::Sym(name) => conditions == sym || *name == symbol::ERROR || *name == symbol::T,
+        Expr::Cons(_, _) => {
+            let mut head = conditions;
+            while let Expr::Cons(car, cdr) = head {
+                if handles(car, sym) {
+                    return true;
+                }
+                head = cdr.deref();
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+// the body of the first handler whose conditions satisfy `matches`
+fn find_handler<F>(handlers: &Expr, matches: F) -> Result<Option<&Expr>>
+where
+    F: Fn(&Expr) -> bool,
+{
+    let mut head = handlers;
+    while let Expr::Cons(handler, rest) = head {
+        match handler.deref() {
+            Expr::Cons(conditions, body) => {
+                if matches(conditions) {
+                    return Ok(Some(body.deref()));
+                }
+            }
+            handler => return Err(E::Form(handler.clone())),
         }
+        head = rest.deref();
     }
+    Ok(None)
+}
+
+fn k_condition_case(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (var, body, handlers) = match args {
+        Expr::Cons(var, rest) => match rest.deref() {
+            Expr::Cons(body, handlers) => (var.deref(), body.deref(), handlers.deref()),
+            _ => return Err(E::Form(args.clone())),
+        },
+        _ => return Err(E::Form(args.clone())),
+    };
+    let depth = env.depth();
+    let (value, handler) = match eval(env, body) {
+        // throws are not errors, and limits must not be escaped
+        Err(e @ E::Throw(_, _)) | Err(e @ E::Limit(_)) => return Err(e),
+        Err(e) => {
+            env.unwind_to(depth);
+            let (sym, data) = e.condition();
+            match find_handler(handlers, |conditions| handles(conditions, &sym))? {
+                Some(handler) => (kcons(sym, data), handler),
+                None => return Err(e),
+            }
+        }
+        Ok(value) => {
+            let success = kkw("success");
+            match find_handler(handlers, |conditions| conditions == &success)? {
+                Some(handler) => (value, handler),
+                None => return Ok(value),
+            }
+        }
+    };
+    match var {
+        Expr::Nil => f_progn(env, handler),
+        var => with_local(env, |env| {
+            bind_name(env, var, value)?;
+            f_progn(env, handler)
+        }),
+    }
+}
+
+// runs the cleanup forms after the body, however it exits, except when it runs out of a limit
+// or is interrupted. Then the cleanup forms are skipped and the evaluation stops.
+fn k_unwind_protect(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (body, unwind) = match args {
+        Expr::Cons(body, unwind) => (body.deref(), unwind.deref()),
+        _ => return Err(E::Form(args.clone())),
+    };
+    let depth = env.depth();
+    let ret = eval(env, body);
+    env.unwind_to(depth);
+    if let Err(e @ E::Limit(_)) = ret {
+        return Err(e);
+    }
+    // an error in the cleanup forms takes precedence
+    f_progn(env, unwind)?;
+    ret
 }
 
 fn feval(env: &mut Env, expr: &Expr) -> Result<Proc> {
     match expr {
-        Expr::Sym(sym) => match env.ffind(sym) {
-            Ok(f) => Ok(f.clone()),
-            Err(e) => Err(e),
-        },
+        Expr::Sym(sym) => env.ffind(sym),
         Expr::Cons(op, rest) => {
             let op = op.deref();
             match op {
-                Expr::Sym(sym) => match &sym[..] {
-                    "lambda" => f_lambda(env, rest.deref()),
-                    _ => Ok(Proc::Expr(Rc::new(eval(env, expr)?))),
-                },
+                Expr::Sym(symbol::LAMBDA) => f_lambda(env, rest.deref()),
+                Expr::Sym(_) => Ok(Proc::Expr(Rc::new(eval(env, expr)?))),
                 _ => Err(E::NotFunction(expr.clone())),
             }
         }
@@ -177,7 +655,7 @@ fn feval(env: &mut Env, expr: &Expr) -> Result<Proc> {
 pub fn macro_fn(env: &mut Env, p: &Proc) -> Result<Option<Proc>> {
     match p {
         Proc::Expr(exp) => match exp.deref() {
-            Expr::Cons(sym, f) => if sym.deref() == &ksym("macro") {
+            Expr::Cons(sym, f) => if sym.deref() == &Expr::Sym(symbol::MACRO) {
                 Ok(Some(feval(env, f.deref())?))
             } else {
                 Ok(None)
@@ -189,18 +667,86 @@ pub fn macro_fn(env: &mut Env, p: &Proc) -> Result<Option<Proc>> {
 }
 
 pub fn eval(env: &mut Env, expr: &Expr) -> Result<Expr> {
+    trampoline(env, Tail::Eval(expr.clone()))
+}
+
+/// evaluates `expr` within `limits`
+pub fn eval_with_limits(env: &mut Env, expr: &Expr, limits: &Limits) -> Result<Expr> {
+    env.with_limits(limits, |env| eval(env, expr))
+}
+
+// special forms whose last form is in tail position
+fn tail_form(name: &Symbol) -> Option<fn(&mut Env, &Expr) -> Result<Tail>> {
+    let form: fn(&mut Env, &Expr) -> Result<Tail> = match *name {
+        symbol::PROGN => k_progn,
+        symbol::IF => k_if,
+        symbol::COND => k_cond,
+        symbol::AND => k_and,
+        symbol::OR => k_or,
+        symbol::LET => k_let,
+        symbol::LET_STAR => k_let_star,
+        symbol::FLET | symbol::CL_FLET => k_flet,
+        symbol::LABELS | symbol::CL_LABELS => k_labels,
+        _ => return None,
+    };
+    Some(form)
+}
+
+fn special_form(name: &Symbol) -> Option<fn(&mut Env, &Expr) -> Result<Expr>> {
+    let form: fn(&mut Env, &Expr) -> Result<Expr> = match *name {
+        symbol::QUOTE => k_quote,
+        symbol::BACKQUOTE => k_backquote,
+        symbol::FUNCTION => k_feval,
+        symbol::LAMBDA => k_lambda,
+        symbol::FSET => k_fset,
+        symbol::SET => k_set,
+        symbol::DEFVAR => k_defvar,
+        symbol::DEFCONST => k_defconst,
+        symbol::WHILE => k_while,
+        symbol::CATCH => k_catch,
+        symbol::CONDITION_CASE => k_condition_case,
+        symbol::UNWIND_PROTECT => k_unwind_protect,
+        _ => return None,
+    };
+    Some(form)
+}
+
+// evaluates a call of a function or a macro
+fn f_call(env: &mut Env, car: &Expr, cdr: &Expr) -> Result<Tail> {
+    let f = feval(env, car)?;
+    if let Some(f) = macro_fn(env, &f)? {
+        return Ok(Tail::Eval(funcall(env, &f, cdr)?));
+    }
+    // arguments are evaluated here rather than with `f_map` to keep the Rust stack shallow
+    let mut args = Vec::new();
+    let mut head = cdr;
+    while let Expr::Cons(arg, rest) = head {
+        args.push(eval(env, arg)?);
+        head = rest.deref();
+    }
+    if head != &knil() {
+        return Err(E::Form(cdr.clone()));
+    }
+    let args = args.into_iter().rev().fold(knil(), |acc, x| kcons(x, acc));
+    Ok(Tail::Call(f, args))
+}
+
+fn eval_step(env: &mut Env, expr: &Expr) -> Result<Tail> {
     match expr {
         Expr::Nil
         | Expr::Str(_)
         | Expr::Int(_)
+        | Expr::Bignum(_)
         | Expr::Float(_)
         | Expr::Keyword(_)
-        | Expr::Proc(_) => Ok(expr.clone()),
-        Expr::Sym(name) => match env.find(&name.to_owned()) {
-            Ok(v) => Ok(v.clone()),
+        | Expr::Vector(_)
+        | Expr::HashTable(_)
+        | Expr::Proc(_) => Ok(Tail::Done(expr.clone())),
+        Expr::Sym(name) => match env.find(name) {
+            Ok(v) => Ok(Tail::Done(v)),
             Err(m) => {
-                if name.deref() == "t" {
-                    Ok(ksym("t"))
+                if *name == symbol::T {
+                    Ok(Tail::Done(Expr::Sym(symbol::T)))
                 } else {
                     Err(m)
                 }
@@ -209,38 +755,16 @@ pub fn eval(env: &mut Env, expr: &Expr) -> Result<Expr> {
         Expr::Cons(car, cdr) => {
             let car = car.deref();
             let cdr = cdr.deref();
-            match car {
-                Expr::Sym(sym) => {
-                    match &sym[..] {
-                        // Eval special forms first
-                        "quote" => k_quote(env, cdr),
-                        "function" => k_feval(env, cdr),
-                        "lambda" => k_lambda(env, cdr),
-                        "progn" => k_progn(env, cdr),
-                        "fset" => k_fset(env, cdr),
-                        "set" => k_set(env, cdr),
-                        "if" => k_if(env, cdr),
-                        _ => {
-                            let f = feval(env, car)?;
-                            match macro_fn(env, &f)? {
-                                Some(f) => {
-                                    let body = funcall(env, &f, cdr)?;
-                                    eval(env, &body)
-                                }
-                                None => {
-                                    let arg = f_map(env, &|env, x| eval(env, x), cdr)?;
-                                    funcall(env, &f, &arg)
-                                }
-                            }
-                        }
-                    }
+            // Eval special forms first
+            if let Expr::Sym(sym) = car {
+                if let Some(form) = tail_form(sym) {
+                    return form(env, cdr);
                 }
-                car => {
-                    let f = feval(env, car)?;
-                    let arg = f_map(env, &|env, x| eval(env, x), cdr)?;
-                    funcall(env, &f, &arg)
+                if let Some(form) = special_form(sym) {
+                    return form(env, cdr).map(Tail::Done);
                 }
             }
+            f_call(env, car, cdr)
         }
     }
 }
@@ -1,27 +1,37 @@
+use std::cell::RefCell;
 use std::convert::From;
 use std::error;
 use std::fmt;
 use std::fmt::{Display, Error as E, Formatter};
-use std::ops::Deref;
+use std::mem;
 use std::rc::Rc;
 use std::result;
 
-use env::Env;
+use num_bigint::BigInt;
+
+use env::{Env, Frame};
+use hash::HashTable;
+use read::ReadError;
+use symbol::Symbol;
 use util::*;
 
-pub type Kfloat = f32;
+pub type Kfloat = f64;
 pub type Kint = isize;
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
     Int(Kint),
+    /// an integer that doesn't fit in `Kint`. Smaller ones are always `Int`.
+    Bignum(Rc<BigInt>),
     Float(Kfloat),
     Cons(Rc<Expr>, Rc<Expr>),
     Nil,
-    Sym(Rc<String>),
+    Sym(Symbol),
     Keyword(Rc<String>),
     Str(Rc<String>),
+    Vector(Rc<RefCell<Vec<Expr>>>),
+    HashTable(Rc<RefCell<HashTable>>),
     Proc(Proc),
 }
 
@@ -29,25 +39,33 @@ pub enum Expr {
 pub enum Type {
     Int,
     Float,
+    Number,
+    Char,
     Cons,
     Nil,
     Sym,
     Keyword,
     Str,
+    Vector,
+    Array,
+    Sequence,
+    HashTable,
     Proc,
     Any,
 }
 
 #[derive(Clone)]
 pub enum Proc {
-    Lambda(Rc<Expr>, Rc<Expr>),
+    /// params, body and the captured lexical environment
+    Lambda(Rc<Expr>, Rc<Expr>, Option<Rc<Frame>>),
     Prim(String, Rc<Fn(&mut Env, &Expr) -> Result<Expr>>),
     Expr(Rc<Expr>),
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
-    ReadError,
+    Read(ReadError),
+    Io(String),
     InvalidArgument(Expr),
     Type(Type, Expr),
     ArityShort,
@@ -56,6 +74,74 @@ pub enum Error {
     NotFunction(Expr),
     Unbound(String),
     User(String),
+    /// non-local exit by `throw` with the tag and the value
+    Throw(Expr, Expr),
+    /// error signaled by `signal` with the error symbol and the data
+    Signal(Expr, Expr),
+    /// the evaluation ran out of its budget. Lisp code cannot catch it.
+    Limit(Limit),
+}
+
+/// the resource that ran out in `Error::Limit`
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Limit {
+    Steps,
+    Depth,
+    Allocation,
+    Timeout,
+    Interrupted,
+}
+
+impl Error {
+    /// the error symbol and the data that `condition-case` sees for this error
+    pub fn condition(&self) -> (Expr, Expr) {
+        match self {
+            Error::Read(e) => (ksym("invalid-read-syntax"), klist!(e.to_string())),
+            Error::Io(e) => (ksym("file-error"), klist!(e.clone())),
+            Error::Type(t, v) => (
+                ksym("wrong-type-argument"),
+                klist!(ksym(t.predicate()), v.clone()),
+            ),
+            Error::ArityShort | Error::ArityExceed => (ksym("wrong-number-of-arguments"), knil()),
+            Error::NotFunction(f) => (ksym("invalid-function"), klist!(f.clone())),
+            Error::Unbound(name) => (ksym("void-variable"), klist!(ksym(name.clone()))),
+            Error::Throw(tag, value) => (ksym("no-catch"), klist!(tag.clone(), value.clone())),
+            Error::Signal(sym, data) => (sym.clone(), data.clone()),
+            Error::Limit(limit) => (ksym("limit-exceeded"), klist!(ksym(limit.to_string()))),
+            e @ Error::InvalidArgument(_) | e @ Error::Form(_) | e @ Error::User(_) => {
+                (ksym("error"), klist!(e.to_string()))
+            }
+        }
+    }
+}
+
+// drops the conses that only `expr` refers to with a loop instead of recursion,
+// so that dropping a long or deeply nested list doesn't overflow the stack
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut conses = Vec::new();
+        take_conses(self, &mut conses);
+        while let Some(mut cons) = conses.pop() {
+            take_conses(&mut cons, &mut conses);
+        }
+    }
+}
+
+// moves the car and the cdr of `expr` that are conses not shared with others to `conses`
+fn take_conses(expr: &mut Expr, conses: &mut Vec<Expr>) {
+    if let Expr::Cons(car, cdr) = expr {
+        for e in [car, cdr].iter_mut().filter_map(|e| Rc::get_mut(e)) {
+            if let Expr::Cons(_, _) = e {
+                conses.push(mem::replace(e, Expr::Nil));
+            }
+        }
+    }
+}
+
+impl From<ReadError> for Error {
+    fn from(e: ReadError) -> Self {
+        Error::Read(e)
+    }
 }
 
 impl From<Kint> for Expr {
@@ -87,8 +173,13 @@ impl<'a> From<&'a Expr> for Expr {
 impl PartialEq for Proc {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Proc::Lambda(param1, body1), Proc::Lambda(param2, body2)) => {
-                param1 == param2 && body1 == body2
+            (Proc::Lambda(param1, body1, env1), Proc::Lambda(param2, body2, env2)) => {
+                let same_env = match (env1, env2) {
+                    (Some(env1), Some(env2)) => Rc::ptr_eq(env1, env2),
+                    (None, None) => true,
+                    _ => false,
+                };
+                param1 == param2 && body1 == body2 && same_env
             }
             _ => false,
         }
@@ -98,44 +189,92 @@ impl PartialEq for Proc {
 impl fmt::Debug for Proc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Proc::Lambda(param, body) => write!(f, "Lambda({}, {})", param, body),
+            Proc::Lambda(param, body, _) => write!(f, "Lambda({}, {})", param, body),
             Proc::Prim(name, _) => write!(f, "Prim(#<native function {}>)", name),
             Proc::Expr(e) => write!(f, "{}", e),
         }
     }
 }
 
-impl fmt::Display for Expr {
+/// formats a float like `prin1` of Emacs: the shortest digits that read back to the same float,
+/// laid out like `%g` with at least 15 significant digits, and with `.0` added to integral values
+pub fn format_float(x: Kfloat) -> String {
+    if x.is_infinite() {
+        return if x > 0.0 { "1.0e+INF" } else { "-1.0e+INF" }.to_string();
+    }
+    if x.is_nan() {
+        return if x.is_sign_negative() {
+            "-0.0e+NaN"
+        } else {
+            "0.0e+NaN"
+        }
+        .to_string();
+    }
+    // `{:e}` gives the shortest round-trip digits as `d.ddde-x`
+    let scientific = format!("{:e}", x.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let precision = ::std::cmp::max(digits.len() as i32, 15);
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if exponent < -4 || exponent >= precision {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exp_sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}{}{}{}e{}{:02}",
+            sign,
+            first,
+            point,
+            rest,
+            exp_sign,
+            exponent.abs()
+        )
+    } else if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        format!("{}0.{}{}", sign, zeros, digits)
+    } else {
+        let int_len = exponent as usize + 1;
+        if digits.len() > int_len {
+            format!("{}{}.{}", sign, &digits[..int_len], &digits[int_len..])
+        } else {
+            let zeros = "0".repeat(int_len - digits.len());
+            format!("{}{}{}.0", sign, digits, zeros)
+        }
+    }
+}
+
+impl Type {
+    /// the name of the predicate of this type, used in `wrong-type-argument`
+    pub fn predicate(&self) -> &'static str {
+        match self {
+            Type::Int => "integerp",
+            Type::Float => "floatp",
+            Type::Number => "numberp",
+            Type::Char => "characterp",
+            Type::Cons => "consp",
+            Type::Nil => "null",
+            Type::Sym => "symbolp",
+            Type::Keyword => "keywordp",
+            Type::Str => "stringp",
+            Type::Vector => "vectorp",
+            Type::Array => "arrayp",
+            Type::Sequence => "sequencep",
+            Type::HashTable => "hash-table-p",
+            Type::Proc => "functionp",
+            Type::Any => "always",
+        }
+    }
+}
+
+impl fmt::Display for Limit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Expr::Int(i) => write!(f, "{}", i),
-            Expr::Float(fl) => write!(f, "{}", fl),
-            // :TODO: pretty print for lists
-            Expr::Cons(car, cdr) => {
-                let mut tmp = cdr.deref();
-                write!(f, "(")?;
-                write!(f, "{}", car)?;
-
-                loop {
-                    match tmp {
-                        Expr::Cons(car, cdr) => {
-                            write!(f, " {}", car)?;
-                            tmp = cdr.deref()
-                        }
-                        Expr::Nil => break,
-                        cdr => {
-                            write!(f, " . {}", cdr)?;
-                            break;
-                        }
-                    }
-                }
-                write!(f, ")")
-            }
-            Expr::Nil => write!(f, "nil"),
-            Expr::Sym(s) => write!(f, "{}", s),
-            Expr::Keyword(s) => write!(f, ":{}", s),
-            Expr::Str(s) => write!(f, "\"{}\"", s),
-            Expr::Proc(p) => write!(f, "{}", p),
+            Limit::Steps => write!(f, "steps"),
+            Limit::Depth => write!(f, "depth"),
+            Limit::Allocation => write!(f, "allocation"),
+            Limit::Timeout => write!(f, "timeout"),
+            Limit::Interrupted => write!(f, "interrupted"),
         }
     }
 }
@@ -145,11 +284,17 @@ impl fmt::Display for Type {
         match self {
             Type::Int => write!(f, "integer"),
             Type::Float => write!(f, "float"),
+            Type::Number => write!(f, "number"),
+            Type::Char => write!(f, "character"),
             Type::Cons => write!(f, "cons"),
             Type::Nil => write!(f, "nil"),
             Type::Sym => write!(f, "symbol"),
             Type::Keyword => write!(f, "keyword"),
             Type::Str => write!(f, "string"),
+            Type::Vector => write!(f, "vector"),
+            Type::Array => write!(f, "array"),
+            Type::Sequence => write!(f, "sequence"),
+            Type::HashTable => write!(f, "hash-table"),
             Type::Proc => write!(f, "procedure"),
             Type::Any => write!(f, "any"),
         }
@@ -159,7 +304,7 @@ impl fmt::Display for Type {
 impl fmt::Display for Proc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Proc::Lambda(args, body) => write!(f, "(lambda {} {})", args, body),
+            Proc::Lambda(args, body, _) => write!(f, "(lambda {} {})", args, body),
             Proc::Prim(name, _) => write!(f, "{}", name),
             Proc::Expr(e) => write!(f, "{}", e),
         }
@@ -169,7 +314,8 @@ impl fmt::Display for Proc {
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> result::Result<(), E> {
         let res = match self {
-            Error::ReadError => write!(f, "read error"),
+            Error::Read(e) => write!(f, "read error: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::InvalidArgument(args) => write!(f, "invalid argument: {}", args),
             Error::Type(t, args) => write!(f, "type mismatch: expected: {}, got: {}", t, args),
             Error::ArityShort => write!(f, "too few argument"),
@@ -178,6 +324,10 @@ impl Display for Error {
             Error::NotFunction(e) => write!(f, "not a function: {}", e),
             Error::Unbound(s) => write!(f, "unbound variable: {}", s),
             Error::User(s) => write!(f, "user error: {}", s),
+            Error::Throw(tag, value) => write!(f, "no catch for tag: {}, {}", tag, value),
+            Error::Signal(sym, data) => write!(f, "{}: {}", sym, data),
+            Error::Limit(Limit::Interrupted) => write!(f, "evaluation interrupted"),
+            Error::Limit(limit) => write!(f, "evaluation limit exceeded: {}", limit),
         };
         res?;
         Ok(())
@@ -0,0 +1,471 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+use env::Env;
+use eval::funcall;
+use expr::{Error as E, Expr, Kint, Proc, Result, Type};
+use util::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HashTest {
+    Eq,
+    Eql,
+    Equal,
+}
+
+impl HashTest {
+    pub fn from_name(name: &str) -> Option<HashTest> {
+        match name {
+            "eq" => Some(HashTest::Eq),
+            "eql" => Some(HashTest::Eql),
+            "equal" => Some(HashTest::Equal),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashTest::Eq => "eq",
+            HashTest::Eql => "eql",
+            HashTest::Equal => "equal",
+        }
+    }
+
+    pub fn test(&self, x: &Expr, y: &Expr) -> bool {
+        match self {
+            HashTest::Eq => is_eq(x, y),
+            HashTest::Eql => is_eql(x, y),
+            // circular keys are never found
+            HashTest::Equal => is_equal(x, y).unwrap_or(false),
+        }
+    }
+
+    fn hash(&self, x: &Expr) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            HashTest::Eq | HashTest::Eql => hash_eq(x, &mut hasher),
+            HashTest::Equal => hash_equal(x, 3, &mut hasher),
+        }
+        hasher.finish()
+    }
+}
+
+fn ptr<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+/// identity of objects. Numbers (bignums included), symbols and keywords are compared by value.
+pub fn is_eq(x: &Expr, y: &Expr) -> bool {
+    match (x, y) {
+        (Expr::Cons(car1, cdr1), Expr::Cons(car2, cdr2)) => {
+            Rc::ptr_eq(car1, car2) && Rc::ptr_eq(cdr1, cdr2)
+        }
+        (Expr::Str(s1), Expr::Str(s2)) => Rc::ptr_eq(s1, s2),
+        (Expr::Vector(v1), Expr::Vector(v2)) => Rc::ptr_eq(v1, v2),
+        (Expr::HashTable(h1), Expr::HashTable(h2)) => Rc::ptr_eq(h1, h2),
+        (Expr::Float(f1), Expr::Float(f2)) => f1.to_bits() == f2.to_bits(),
+        (Expr::Proc(p1), Expr::Proc(p2)) => proc_identity(p1) == proc_identity(p2),
+        (x, y) => x == y,
+    }
+}
+
+// the address that tells functions apart. Copies of a function share it.
+fn proc_identity(p: &Proc) -> usize {
+    match p {
+        Proc::Lambda(_, body, _) => ptr(body),
+        Proc::Prim(_, f) => ptr(f),
+        Proc::Expr(e) => ptr(e),
+    }
+}
+
+/// floats are compared by their bits. Since floats are not boxed, this is the same as `is_eq`.
+pub fn is_eql(x: &Expr, y: &Expr) -> bool {
+    is_eq(x, y)
+}
+
+// the identity of a cons or a vector, like `is_eq`
+type Id = (usize, usize);
+
+fn identity(x: &Expr) -> Id {
+    match x {
+        Expr::Cons(car, cdr) => (ptr(car), ptr(cdr)),
+        Expr::Vector(v) => (ptr(v), 0),
+        _ => (0, 0),
+    }
+}
+
+/// similarity of structures. Floats are compared by their bits like `is_eql`.
+/// Fails with `circular-list` if it meets a cycle, on which it would never end.
+pub fn is_equal(x: &Expr, y: &Expr) -> Result<bool> {
+    // the pairs left to compare, with their depths. A stack is used instead of recursion so that
+    // deep structures cannot overflow the Rust stack.
+    let mut pending = vec![(x.clone(), y.clone(), 0)];
+    // the pairs of conses and vectors whose elements are being compared
+    let mut path = Vec::new();
+    let mut on_path = HashSet::new();
+    while let Some((x, y, depth)) = pending.pop() {
+        for pair in path.drain(depth..) {
+            on_path.remove(&pair);
+        }
+        match (&x, &y) {
+            _ if is_eq(&x, &y) => (),
+            (Expr::Cons(car1, cdr1), Expr::Cons(car2, cdr2)) => {
+                enter(&x, &y, &mut path, &mut on_path)?;
+                // the cdrs are not deeper so that long lists don't count as deep
+                pending.push((cdr1.deref().clone(), cdr2.deref().clone(), depth));
+                pending.push((car1.deref().clone(), car2.deref().clone(), depth + 1));
+            }
+            (Expr::Vector(v1), Expr::Vector(v2)) => {
+                let (v1, v2) = (v1.borrow(), v2.borrow());
+                if v1.len() != v2.len() {
+                    return Ok(false);
+                }
+                enter(&x, &y, &mut path, &mut on_path)?;
+                let elements = v1.iter().zip(v2.iter()).rev();
+                pending.extend(elements.map(|(x, y)| (x.clone(), y.clone(), depth + 1)));
+            }
+            // tables and floats are equal only if they are `eq`. Tables are not borrowed, as a
+            // table may be comparing its own keys.
+            (Expr::HashTable(_), Expr::HashTable(_)) | (Expr::Float(_), Expr::Float(_)) => {
+                return Ok(false)
+            }
+            (x, y) if x != y => return Ok(false),
+            _ => (),
+        }
+    }
+    Ok(true)
+}
+
+// adds the pair of `x` and `y` to the path, failing if they are already being compared
+fn enter(
+    x: &Expr,
+    y: &Expr,
+    path: &mut Vec<(Id, Id)>,
+    on_path: &mut HashSet<(Id, Id)>,
+) -> Result<()> {
+    let pair = (identity(x), identity(y));
+    if !on_path.insert(pair) {
+        return Err(E::Signal(ksym("circular-list"), klist!(x.clone())));
+    }
+    path.push(pair);
+    Ok(())
+}
+
+fn hash_eq<H: Hasher>(x: &Expr, state: &mut H) {
+    match x {
+        Expr::Int(i) => i.hash(state),
+        Expr::Bignum(i) => i.hash(state),
+        Expr::Float(f) => f.to_bits().hash(state),
+        Expr::Cons(car, _) => ptr(car).hash(state),
+        Expr::Nil => 0u8.hash(state),
+        Expr::Sym(s) => s.hash(state),
+        Expr::Keyword(s) => s.hash(state),
+        Expr::Str(s) => ptr(s).hash(state),
+        Expr::Vector(v) => ptr(v).hash(state),
+        Expr::HashTable(h) => ptr(h).hash(state),
+        Expr::Proc(p) => proc_identity(p).hash(state),
+    }
+}
+
+// hashes the structure up to `depth` levels like `sxhash-equal`
+fn hash_equal<H: Hasher>(x: &Expr, depth: usize, state: &mut H) {
+    match x {
+        Expr::Cons(_, _) if depth > 0 => {
+            let mut head = x;
+            let mut n = 0;
+            while let (Expr::Cons(car, cdr), true) = (head, n < 7) {
+                hash_equal(car, depth - 1, state);
+                head = cdr.deref();
+                n += 1;
+            }
+        }
+        Expr::Cons(_, _) => 1u8.hash(state),
+        Expr::Str(s) => s.hash(state),
+        Expr::Vector(v) if depth > 0 => {
+            for e in v.borrow().iter().take(7) {
+                hash_equal(e, depth - 1, state);
+            }
+        }
+        Expr::Vector(_) => 2u8.hash(state),
+        Expr::Float(f) => f.to_bits().hash(state),
+        Expr::HashTable(_) => 3u8.hash(state),
+        x => hash_eq(x, state),
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    key: Expr,
+    value: Expr,
+    // insertion order
+    seq: usize,
+}
+
+/// where a key is in a `HashTable`, found by `HashTable::find`
+pub struct Place {
+    hash: u64,
+    index: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct HashTable {
+    test: HashTest,
+    buckets: HashMap<u64, Vec<Entry>>,
+    count: usize,
+    next_seq: usize,
+}
+
+impl HashTable {
+    pub fn new(test: HashTest) -> Self {
+        HashTable {
+            test,
+            buckets: HashMap::new(),
+            count: 0,
+            next_seq: 0,
+        }
+    }
+
+    pub fn test(&self) -> HashTest {
+        self.test
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn get(&self, key: &Expr) -> Option<&Expr> {
+        let test = self.test;
+        self.buckets
+            .get(&test.hash(key))
+            .and_then(|bucket| bucket.iter().find(|e| test.test(&e.key, key)))
+            .map(|e| &e.value)
+    }
+
+    /// looks `key` up without modifying the table, so that the keys can be compared while
+    /// the table is only borrowed. The place is valid until the table is modified.
+    pub fn find(&self, key: &Expr) -> Place {
+        let test = self.test;
+        let hash = test.hash(key);
+        let index = self
+            .buckets
+            .get(&hash)
+            .and_then(|bucket| bucket.iter().position(|e| test.test(&e.key, key)));
+        Place { hash, index }
+    }
+
+    pub fn insert(&mut self, key: Expr, value: Expr) {
+        let place = self.find(&key);
+        self.insert_at(place, key, value)
+    }
+
+    /// sets the value of `key`, which is at `place`
+    pub fn insert_at(&mut self, place: Place, key: Expr, value: Expr) {
+        let bucket = self.buckets.entry(place.hash).or_default();
+        match place.index {
+            Some(i) => bucket[i].value = value,
+            None => {
+                bucket.push(Entry {
+                    key,
+                    value,
+                    seq: self.next_seq,
+                });
+                self.next_seq += 1;
+                self.count += 1;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &Expr) -> Option<Expr> {
+        let place = self.find(key);
+        self.remove_at(place)
+    }
+
+    /// removes the entry at `place`
+    pub fn remove_at(&mut self, place: Place) -> Option<Expr> {
+        let i = place.index?;
+        let bucket = self.buckets.get_mut(&place.hash)?;
+        let removed = bucket.remove(i).value;
+        if bucket.is_empty() {
+            self.buckets.remove(&place.hash);
+        }
+        self.count -= 1;
+        Some(removed)
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.count = 0;
+    }
+
+    /// key-value pairs in insertion order
+    pub fn entries(&self) -> Vec<(Expr, Expr)> {
+        let mut entries: Vec<&Entry> = self.buckets.values().flat_map(|b| b.iter()).collect();
+        entries.sort_by_key(|e| e.seq);
+        entries
+            .into_iter()
+            .map(|e| (e.key.clone(), e.value.clone()))
+            .collect()
+    }
+}
+
+/// hash tables are `equal` only to themselves, as in Emacs
+impl PartialEq for HashTable {
+    fn eq(&self, other: &Self) -> bool {
+        ::std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HashTable({:?}, {:?})", self.test, self.entries())
+    }
+}
+
+// accepts both `'equal` and `#'equal`. The function is the one bound to the name of the test.
+fn test_of(env: &Env, test: &Expr) -> Result<HashTest> {
+    let test_fn = match test {
+        Expr::Sym(name) => HashTest::from_name(&name.name()),
+        Expr::Proc(Proc::Prim(_, f)) => [HashTest::Eq, HashTest::Eql, HashTest::Equal]
+            .iter()
+            .cloned()
+            .find(|t| match env.ffind(t.name()) {
+                Ok(Proc::Prim(_, g)) => Rc::ptr_eq(f, &g),
+                _ => false,
+            }),
+        _ => None,
+    };
+    match test_fn {
+        Some(test) => Ok(test),
+        None => Err(E::InvalidArgument(test.clone())),
+    }
+}
+
+/// builds a hash table from the contents of `#s(hash-table ...)`
+pub fn from_literal(params: &Expr) -> Option<HashTable> {
+    let mut table = HashTable::new(HashTest::Eql);
+    let mut data = &knil();
+    let mut head = params;
+    while let Expr::Cons(key, rest) = head {
+        let (value, rest) = match rest.deref() {
+            Expr::Cons(value, rest) => (value.deref(), rest.deref()),
+            _ => return None,
+        };
+        match (key.deref(), value) {
+            (Expr::Sym(k), Expr::Sym(test)) if &*k.name() == "test" => {
+                table = HashTable::new(HashTest::from_name(&test.name())?)
+            }
+            (Expr::Sym(k), data_list) if &*k.name() == "data" => data = data_list,
+            (Expr::Sym(_), _) => (),
+            _ => return None,
+        }
+        head = rest;
+    }
+    let mut head = data;
+    while let Expr::Cons(key, rest) = head {
+        match rest.deref() {
+            Expr::Cons(value, rest) => {
+                table.insert(key.deref().clone(), value.deref().clone());
+                head = rest.deref();
+            }
+            _ => return None,
+        }
+    }
+    Some(table)
+}
+
+pub fn k_make_hash_table(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let mut test = HashTest::Eql;
+    let mut head = args;
+    while let Expr::Cons(key, rest) = head {
+        let (value, rest) = match rest.deref() {
+            Expr::Cons(value, rest) => (value.deref(), rest.deref()),
+            _ => return Err(E::InvalidArgument(args.clone())),
+        };
+        match key.deref() {
+            Expr::Keyword(k) if &k[..] == "test" => test = test_of(env, value)?,
+            Expr::Keyword(k) => match &k[..] {
+                "size" | "weakness" | "rehash-size" | "rehash-threshold" | "purecopy" => (),
+                _ => return Err(E::InvalidArgument(key.deref().clone())),
+            },
+            key => return Err(E::Type(Type::Keyword, key.clone())),
+        }
+        head = rest;
+    }
+    Ok(khash_table(HashTable::new(test)))
+}
+
+pub fn k_gethash(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (key, Any)(table, HashTable) & optional(default, Any));
+    match table.borrow().get(key) {
+        Some(v) => Ok(v.clone()),
+        None => Ok(default.cloned().unwrap_or_else(knil)),
+    }
+}
+
+pub fn k_puthash(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (key, Any)(value, Any)(table, HashTable));
+    // the keys may contain the table itself
+    let place = table.borrow().find(key);
+    table
+        .borrow_mut()
+        .insert_at(place, key.clone(), value.clone());
+    Ok(value.clone())
+}
+
+pub fn k_remhash(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (key, Any)(table, HashTable));
+    let place = table.borrow().find(key);
+    table.borrow_mut().remove_at(place);
+    Ok(knil())
+}
+
+pub fn k_clrhash(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (table, HashTable));
+    table.borrow_mut().clear();
+    Ok(Expr::HashTable(table.clone()))
+}
+
+pub fn k_maphash(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (f, Any)(table, HashTable));
+    let f = match f {
+        Expr::Proc(f) => f.clone(),
+        Expr::Sym(name) => env.ffind(name)?,
+        f => return Err(E::NotFunction(f.clone())),
+    };
+    // the function may modify the table
+    let entries = table.borrow().entries();
+    for (k, v) in entries {
+        funcall(env, &f, &klist!(k, v))?;
+    }
+    Ok(knil())
+}
+
+pub fn k_hash_table_count(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (table, HashTable));
+    Ok(kint(table.borrow().len() as Kint))
+}
+
+pub fn init(env: &mut Env) -> Result<()> {
+    env.fregister(
+        "make-hash-table",
+        kprim("k_make_hash_table", k_make_hash_table),
+    );
+    env.fregister("gethash", kprim("k_gethash", k_gethash));
+    env.fregister("puthash", kprim("k_puthash", k_puthash));
+    env.fregister("remhash", kprim("k_remhash", k_remhash));
+    env.fregister("clrhash", kprim("k_clrhash", k_clrhash));
+    env.fregister("maphash", kprim("k_maphash", k_maphash));
+    env.fregister(
+        "hash-table-count",
+        kprim("k_hash_table_count", k_hash_table_count),
+    );
+    Ok(())
+}
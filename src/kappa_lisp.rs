@@ -1,25 +1,181 @@
 use base;
 use datetime;
-pub use env::Env;
-pub use eval::eval;
-pub use expr::{Expr, Proc, Result};
-pub use read::read;
+use hash;
+pub use env::{Env, InterruptHandle, Limits};
+pub use eval::{eval, eval_with_limits};
+pub use expr::{Expr, Limit, Proc, Result};
+use pp;
+use print;
+pub use read::{read, ReadBuffer, Reader};
+use sequence;
 use skk;
 use stdlib;
+use symbol;
+
+// installs the modules that neither read the clock nor do I/O. New modules are left out
+// until they are known to be pure.
+fn init_pure(env: &mut Env) -> Result<()> {
+    base::init(env)?;
+    symbol::init(env)?;
+    print::init(env)?;
+    pp::init_pure(env)?;
+    sequence::init(env)?;
+    hash::init(env)?;
+    stdlib::init(env)?;
+    Ok(())
+}
 
 pub fn init(env: &mut Env) -> Result<()> {
     base::init(env)?;
+    symbol::init(env)?;
+    print::init(env)?;
+    pp::init(env)?;
+    sequence::init(env)?;
+    hash::init(env)?;
     datetime::init(env)?;
     stdlib::init(env)?;
     skk::init(env)?;
     Ok(())
 }
 
+/// the set of primitives an `Env` exposes
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Profile {
+    /// computation only. Nothing reads the clock or does I/O.
+    Pure,
+    /// only the functions that candidates in SKK-JISYO.lisp use
+    SkkCandidate,
+    /// everything `init` installs
+    Full,
+}
+
+// the functions kept by `Profile::SkkCandidate`, including the ones the kept Lisp functions call
+const SKK_CANDIDATE_FUNCTIONS: &[&str] = &[
+    "list",
+    "car",
+    "cdr",
+    "cons",
+    "assoc",
+    "concat",
+    "substring",
+    "char-to-string",
+    "string-to-char",
+    "+",
+    "-",
+    "*",
+    "/",
+    "=",
+    "/=",
+    "<",
+    "<=",
+    ">",
+    ">=",
+    "eq",
+    "eql",
+    "equal",
+    "equalp",
+    "not",
+    "null",
+    "when",
+    "unless",
+    "setq",
+    "funcall",
+    "error",
+    "format",
+    "signal",
+    "throw",
+    "ignore-errors",
+    "boundp",
+    "symbol-value",
+    "current-time",
+    "current-time-string",
+    "skk-calc",
+    "skk-current-date",
+    "skk-current-date-1",
+    "skk-gadget-units-conversion",
+];
+
+/// builds an `Env` with a profile
+///
+/// ```
+/// use kappa_lisp::{EnvBuilder, Profile};
+///
+/// let env = EnvBuilder::new()
+///     .profile(Profile::SkkCandidate)
+///     .protect_globals(true)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct EnvBuilder {
+    profile: Profile,
+    protect_globals: bool,
+    protected: Vec<String>,
+}
+
+impl EnvBuilder {
+    pub fn new() -> Self {
+        EnvBuilder {
+            profile: Profile::Full,
+            protect_globals: false,
+            protected: Vec::new(),
+        }
+    }
+
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// protects every global variable and function the profile defines,
+    /// so that evaluated code cannot redefine them for later evaluations
+    pub fn protect_globals(mut self, protect: bool) -> Self {
+        self.protect_globals = protect;
+        self
+    }
+
+    /// protects `name` in addition
+    pub fn protect<S: Into<String>>(mut self, name: S) -> Self {
+        self.protected.push(name.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Env> {
+        let mut env = Env::new();
+        match self.profile {
+            Profile::Pure => init_pure(&mut env)?,
+            Profile::SkkCandidate => {
+                init(&mut env)?;
+                env.retain_functions(|name| SKK_CANDIDATE_FUNCTIONS.contains(&name));
+            }
+            Profile::Full => init(&mut env)?,
+        }
+        if self.protect_globals {
+            env.protect_globals();
+        }
+        for name in self.protected {
+            env.protect(name);
+        }
+        Ok(env)
+    }
+}
+
+impl Default for EnvBuilder {
+    fn default() -> Self {
+        EnvBuilder::new()
+    }
+}
+
 pub fn run(env: &mut Env, sexp: &str) -> Result<Expr> {
     let expr = read(sexp)?;
     eval(env, &expr)
 }
 
+/// like `run` but within `limits`
+pub fn run_with_limits(env: &mut Env, sexp: &str, limits: &Limits) -> Result<Expr> {
+    let expr = read(sexp)?;
+    eval_with_limits(env, &expr, limits)
+}
+
 pub fn run_new(sexp: &str) -> Result<Expr> {
     let mut env = Env::new();
     init(&mut env)?;
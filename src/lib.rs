@@ -1,3 +1,7 @@
+extern crate num_bigint;
+extern crate num_integer;
+extern crate num_traits;
+
 #[macro_use]
 pub mod util;
 pub mod base;
@@ -5,8 +9,13 @@ pub mod datetime;
 pub mod env;
 pub mod eval;
 pub mod expr;
+pub mod hash;
 pub mod kappa_lisp;
+pub mod pp;
+pub mod print;
 pub mod read;
+pub mod sequence;
 pub mod skk;
 pub mod stdlib;
+pub mod symbol;
 pub use kappa_lisp::*;
@@ -0,0 +1,473 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::rc::Rc;
+
+use env::Env;
+use expr::{Error as E, Expr, Result};
+use print::{prin1_in, quote_prefix, Path};
+use symbol::{self, Symbol, SymbolMap};
+use util::*;
+
+// the number of distinguished arguments of the forms, like `lisp-indent-function` of Emacs.
+// The rest of the arguments are the body.
+const INDENTS: &[(&str, usize)] = &[
+    ("defun", 2),
+    ("defmacro", 2),
+    ("lambda", 1),
+    ("progn", 0),
+    ("if", 2),
+    ("when", 1),
+    ("unless", 1),
+    ("while", 1),
+    ("let", 1),
+    ("let*", 1),
+    ("flet", 1),
+    ("cl-flet", 1),
+    ("labels", 1),
+    ("cl-labels", 1),
+    ("dolist", 1),
+    ("dotimes", 1),
+    ("catch", 1),
+    ("condition-case", 2),
+    ("unwind-protect", 1),
+];
+
+/// lays out an `Expr` over lines of at most `width` columns where it can.
+/// Forms with an indentation rule keep their distinguished arguments on the first line and
+/// indent the body by 2, and the other lists align their elements.
+///
+/// ```
+/// use kappa_lisp::pp::PrettyPrinter;
+/// use kappa_lisp::read;
+///
+/// let form = read("(defun f (x) (if (< x 0) (- x) x))").unwrap();
+/// assert_eq!(
+///     PrettyPrinter::new().width(24).print(&form),
+///     "(defun f (x)\n  (if (< x 0) (- x) x))"
+/// );
+/// ```
+pub struct PrettyPrinter {
+    width: usize,
+    indents: SymbolMap<usize>,
+}
+
+impl PrettyPrinter {
+    /// a printer for 70 columns that knows the special forms and the macros of stdlib
+    pub fn new() -> Self {
+        let mut printer = PrettyPrinter {
+            width: 70,
+            indents: SymbolMap::default(),
+        };
+        for &(name, specials) in INDENTS {
+            printer.indents.insert(symbol::intern(name), specials);
+        }
+        printer
+    }
+
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// indents the forms headed by `name` with `specials` distinguished arguments
+    pub fn indent<S: Into<Symbol>>(mut self, name: S, specials: usize) -> Self {
+        self.indents.insert(name.into(), specials);
+        self
+    }
+
+    pub fn print(&self, expr: &Expr) -> String {
+        self.print_charged(expr, |_| Ok(())).unwrap()
+    }
+
+    /// like `print`, but calls `charge` for each object measured and before each part written,
+    /// with the length the output will have, so that an evaluation can limit the work
+    pub fn print_charged<F>(&self, expr: &Expr, charge: F) -> Result<String>
+    where
+        F: FnMut(usize) -> Result<()>,
+    {
+        let mut layout = Layout {
+            printer: self,
+            path: Path::new(),
+            widths: HashMap::new(),
+            charge,
+            out: String::new(),
+        };
+        layout.layout(expr, 0, 0)?;
+        Ok(layout.out)
+    }
+}
+
+// the state of laying out an object
+struct Layout<'a, F> {
+    printer: &'a PrettyPrinter,
+    // the objects that the one being laid out is in, as in `print`
+    path: Path,
+    // the widths of the objects printed on one line, by their addresses and depths,
+    // so that each object is measured once
+    widths: HashMap<(usize, usize), usize>,
+    charge: F,
+    out: String,
+}
+
+impl<'a, F: FnMut(usize) -> Result<()>> Layout<'a, F> {
+    // the number of characters of `expr` printed on one line
+    fn width(&mut self, expr: &Expr) -> Result<usize> {
+        let key = (expr as *const Expr as usize, self.path.depth());
+        if let Some(&width) = self.widths.get(&key) {
+            return Ok(width);
+        }
+        (self.charge)(self.out.len())?;
+        let width = match expr {
+            _ if !is_breakable(expr, &self.path) => prin1_in(expr, &mut self.path).chars().count(),
+            Expr::Cons(car, cdr) => {
+                self.path.enter(expr);
+                let width = self.list_width(car, cdr);
+                self.path.leave();
+                width?
+            }
+            Expr::Vector(v) => {
+                self.path.enter(expr);
+                let width = self.elements_width(v.borrow().iter(), None);
+                self.path.leave();
+                width? + 2
+            }
+            _ => unreachable!(),
+        };
+        self.widths.insert(key, width);
+        Ok(width)
+    }
+
+    fn list_width(&mut self, car: &Expr, cdr: &Expr) -> Result<usize> {
+        match quoted(car, cdr) {
+            Some((prefix, arg)) if is_breakable(arg, &self.path) => {
+                Ok(prefix.len() + self.width(arg)?)
+            }
+            // `, @x` has a space
+            Some((_, arg)) => {
+                let quoted = klist!(car.clone(), arg.clone());
+                Ok(prin1_in(&quoted, &mut self.path).chars().count())
+            }
+            None => {
+                let (args, tail) = list_elements(cdr);
+                let elements = Some(car).into_iter().chain(args);
+                Ok(self.elements_width(elements, tail)? + 2)
+            }
+        }
+    }
+
+    // the width of `elements` and the dotted `tail` separated by spaces
+    fn elements_width<'e, I>(&mut self, elements: I, tail: Option<&Expr>) -> Result<usize>
+    where
+        I: Iterator<Item = &'e Expr>,
+    {
+        let mut width = 0usize;
+        for (i, e) in elements.enumerate() {
+            width = width.saturating_add(self.width(e)? + if i == 0 { 0 } else { 1 });
+        }
+        if let Some(tail) = tail {
+            width = width.saturating_add(self.width(tail)? + 3);
+        }
+        Ok(width)
+    }
+
+    // writes `expr` on one line
+    fn write_flat(&mut self, expr: &Expr) -> Result<()> {
+        let width = self.width(expr)?;
+        (self.charge)(self.out.len().saturating_add(width))?;
+        let flat = prin1_in(expr, &mut self.path);
+        self.out.push_str(&flat);
+        Ok(())
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self, column: usize) {
+        self.out.push('\n');
+        self.out.extend((0..column).map(|_| ' '));
+    }
+
+    // writes `expr` starting at `column`, followed by `trail` characters of closing parens
+    fn layout(&mut self, expr: &Expr, column: usize, trail: usize) -> Result<()> {
+        let width = self.width(expr)?;
+        if column + width + trail <= self.printer.width || !is_breakable(expr, &self.path) {
+            return self.write_flat(expr);
+        }
+        self.path.enter(expr);
+        let ret = match expr {
+            Expr::Cons(car, cdr) => match quoted(car, cdr) {
+                Some((prefix, arg)) if is_breakable(arg, &self.path) => {
+                    self.push_str(prefix);
+                    self.layout(arg, column + prefix.len(), trail)
+                }
+                Some(_) => {
+                    self.path.leave();
+                    let ret = self.write_flat(expr);
+                    self.path.enter(expr);
+                    ret
+                }
+                None => self.layout_list(car, cdr, column, trail),
+            },
+            Expr::Vector(v) => {
+                let v = v.borrow();
+                let elements: Vec<&Expr> = v.iter().collect();
+                self.push_str("[");
+                let ret = self.layout_aligned(&elements, None, column + 1, trail + 1);
+                self.push_str("]");
+                ret
+            }
+            _ => unreachable!(),
+        };
+        self.path.leave();
+        ret
+    }
+
+    fn layout_list(&mut self, car: &Expr, cdr: &Expr, column: usize, trail: usize) -> Result<()> {
+        let (args, tail) = list_elements(cdr);
+        self.push_str("(");
+        let head = match car {
+            Expr::Sym(head) if !args.is_empty() => head,
+            _ => {
+                let mut elements = vec![car];
+                elements.extend(args);
+                self.layout_aligned(&elements, tail, column + 1, trail + 1)?;
+                self.push_str(")");
+                return Ok(());
+            }
+        };
+        let head_flat = car.prin1().to_string();
+        self.push_str(&head_flat);
+        let arg_column = column + 1 + head_flat.chars().count() + 1;
+        match self.printer.indents.get(head) {
+            Some(&specials) => {
+                let specials = ::std::cmp::min(specials, args.len());
+                let (specials, body) = args.split_at(specials);
+                let specials_trail = if body.is_empty() && tail.is_none() {
+                    trail + 1
+                } else {
+                    0
+                };
+                self.layout_specials(specials, column, arg_column, specials_trail)?;
+                for (i, e) in body.iter().enumerate() {
+                    self.newline(column + 2);
+                    let last = i == body.len() - 1 && tail.is_none();
+                    self.layout(e, column + 2, if last { trail + 1 } else { 0 })?;
+                }
+                if let Some(tail) = tail {
+                    self.newline(column + 2);
+                    self.push_str(". ");
+                    self.layout(tail, column + 4, trail + 1)?;
+                }
+            }
+            None => {
+                self.push_str(" ");
+                self.layout_aligned(&args, tail, arg_column, trail + 1)?;
+            }
+        }
+        self.push_str(")");
+        Ok(())
+    }
+
+    // the distinguished arguments go on the first line if they fit, and below it indented by 4 otherwise
+    fn layout_specials(
+        &mut self,
+        specials: &[&Expr],
+        column: usize,
+        arg_column: usize,
+        trail: usize,
+    ) -> Result<()> {
+        let mut len = 0;
+        for e in specials {
+            len += self.width(e)? + 1;
+        }
+        if arg_column - 1 + len + trail <= self.printer.width {
+            for e in specials {
+                self.push_str(" ");
+                self.write_flat(e)?;
+            }
+            return Ok(());
+        }
+        for (i, e) in specials.iter().enumerate() {
+            let trail = if i == specials.len() - 1 { trail } else { 0 };
+            if i == 0 {
+                self.push_str(" ");
+                self.layout(e, arg_column, trail)?;
+            } else {
+                self.newline(column + 4);
+                self.layout(e, column + 4, trail)?;
+            }
+        }
+        Ok(())
+    }
+
+    // writes `elements` and the dotted `tail` aligned at `column`.
+    // Lists and vectors take lines of their own, and atoms fill the lines.
+    fn layout_aligned(
+        &mut self,
+        elements: &[&Expr],
+        tail: Option<&Expr>,
+        column: usize,
+        trail: usize,
+    ) -> Result<()> {
+        // the end of the line if it ends with an atom
+        let mut end = None;
+        for (i, e) in elements.iter().enumerate() {
+            let last = i == elements.len() - 1 && tail.is_none();
+            let trail = if last { trail } else { 0 };
+            if is_breakable(e, &self.path) {
+                if i != 0 {
+                    self.newline(column);
+                }
+                self.layout(e, column, trail)?;
+                end = None;
+                continue;
+            }
+            let len = self.width(e)?;
+            end = match end {
+                Some(end) if end + 1 + len + trail <= self.printer.width => {
+                    self.push_str(" ");
+                    Some(end + 1 + len)
+                }
+                _ if i == 0 => Some(column + len),
+                _ => {
+                    self.newline(column);
+                    Some(column + len)
+                }
+            };
+            self.write_flat(e)?;
+        }
+        if let Some(tail) = tail {
+            self.newline(column);
+            self.push_str(". ");
+            self.layout(tail, column + 2, trail)?;
+        }
+        Ok(())
+    }
+}
+
+// the elements of a list and its dotted tail
+fn list_elements(list: &Expr) -> (Vec<&Expr>, Option<&Expr>) {
+    let mut elements = Vec::new();
+    let mut tail = list;
+    while let Expr::Cons(e, rest) = tail {
+        elements.push(e.deref());
+        tail = rest.deref();
+    }
+    match tail {
+        Expr::Nil => (elements, None),
+        tail => (elements, Some(tail)),
+    }
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        PrettyPrinter::new()
+    }
+}
+
+// the reader macro and the argument of `(quote x)` and the like
+fn quoted<'a>(car: &Expr, cdr: &'a Expr) -> Option<(&'static str, &'a Expr)> {
+    match (car, cdr) {
+        (Expr::Sym(sym), Expr::Cons(arg, rest)) if rest.deref() == &Expr::Nil => {
+            quote_prefix(sym).map(|prefix| (prefix, arg.deref()))
+        }
+        _ => None,
+    }
+}
+
+// lists and vectors can span lines, unless they are printed as `#N` or `...`
+fn is_breakable(expr: &Expr, path: &Path) -> bool {
+    matches!(expr, Expr::Cons(_, _) | Expr::Vector(_))
+        && path.find(expr).is_none()
+        && !path.is_full()
+}
+
+// adds the `lisp-indent-function` properties of the heads of the forms in `expr` to `printer`.
+// `defun` as the property means 2 like Emacs. Each vector is visited once, so that circular
+// ones end, and each object visited counts as an evaluation step.
+fn with_indents(env: &mut Env, expr: &Expr, mut printer: PrettyPrinter) -> Result<PrettyPrinter> {
+    let prop = ksym("lisp-indent-function");
+    let mut visited = HashSet::new();
+    let mut exprs = vec![expr.clone()];
+    while let Some(expr) = exprs.pop() {
+        env.step()?;
+        match &expr {
+            Expr::Cons(car, cdr) => {
+                if let Expr::Sym(head) = car.deref() {
+                    match env.get(head, &prop) {
+                        Expr::Int(n) if n >= 0 => printer = printer.indent(head, n as usize),
+                        Expr::Sym(ref s) if s.name().deref() == "defun" => {
+                            printer = printer.indent(head, 2)
+                        }
+                        Expr::Nil => {
+                            printer.indents.remove(head);
+                        }
+                        _ => (),
+                    }
+                }
+                exprs.push(car.deref().clone());
+                exprs.push(cdr.deref().clone());
+            }
+            Expr::Vector(v) if visited.insert(Rc::as_ptr(v)) => {
+                exprs.extend(v.borrow().iter().cloned())
+            }
+            _ => (),
+        }
+    }
+    Ok(printer)
+}
+
+fn printer_of(env: &Env) -> PrettyPrinter {
+    let width = match env.find("fill-column") {
+        Ok(Expr::Int(width)) if width > 0 => width as usize,
+        _ => 70,
+    };
+    PrettyPrinter::new().width(width)
+}
+
+// measuring an object counts as an evaluation step, and the output is charged to the allocation
+// budget before it is written
+fn print_charged(env: &mut Env, printer: &PrettyPrinter, object: &Expr) -> Result<String> {
+    printer.print_charged(object, |size| {
+        env.step()?;
+        env.reserve(size)
+    })
+}
+
+fn k_pp_to_string(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (object, Any));
+    let printer = printer_of(env);
+    let printer = with_indents(env, object, printer)?;
+    let printed = print_charged(env, &printer, object)?;
+    Ok(kstr(printed + "\n"))
+}
+
+fn k_pp(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (object, Any));
+    let printer = printer_of(env);
+    let printer = with_indents(env, object, printer)?;
+    let printed = print_charged(env, &printer, object)?;
+    let stdout = io::stdout();
+    writeln!(stdout.lock(), "{}", printed).map_err(|e| E::Io(e.to_string()))?;
+    Ok(knil())
+}
+
+/// installs the pretty printer without `pp`, which writes to the standard output
+pub fn init_pure(env: &mut Env) -> Result<()> {
+    let lisp_indent_function = ksym("lisp-indent-function");
+    for &(name, specials) in INDENTS {
+        env.put(name, lisp_indent_function.clone(), kint(specials as isize))?;
+    }
+    env.make_special("fill-column");
+    env.register("fill-column", kint(70));
+    env.fregister("pp-to-string", kprim("k_pp_to_string", k_pp_to_string));
+    Ok(())
+}
+
+pub fn init(env: &mut Env) -> Result<()> {
+    init_pure(env)?;
+    env.fregister("pp", kprim("k_pp", k_pp));
+    Ok(())
+}
@@ -0,0 +1,611 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::str::Chars;
+
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+
+use env::Env;
+use expr::{format_float, Error as E, Expr, Kfloat, Result, Type};
+use hash::HashTest;
+use read::{is_delimiter, is_number_syntax};
+use symbol;
+use util::*;
+
+/// an `Expr` that `Display` formats in one of the ways Emacs prints objects.
+/// See `Expr::prin1` and `Expr::princ`.
+pub struct Printer<'a> {
+    expr: &'a Expr,
+    escape: bool,
+}
+
+impl Expr {
+    /// the representation that reads back to an equal object, like `prin1` of Emacs.
+    /// Strings are quoted and escaped, and so are the special characters in symbol names.
+    /// It is the same as `Display` of `Expr`.
+    pub fn prin1(&self) -> Printer<'_> {
+        Printer {
+            expr: self,
+            escape: true,
+        }
+    }
+
+    /// the representation for humans, like `princ` of Emacs.
+    /// Strings and symbol names are written as they are.
+    pub fn princ(&self) -> Printer<'_> {
+        Printer {
+            expr: self,
+            escape: false,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Printer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print(self.expr, self.escape, &mut Path::new(), f)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print(self, true, &mut Path::new(), f)
+    }
+}
+
+// nesting of objects over which they are printed as `...` like `print-level` of Emacs,
+// so that printing, which recurses, cannot overflow the stack
+const MAX_PRINT_DEPTH: usize = 200;
+
+/// the conses, vectors and hash tables being printed, from the outermost one.
+/// An object in its own contents is printed as `#N`, where N is its index in the path, like Emacs.
+pub(crate) struct Path(Vec<usize>);
+
+impl Path {
+    pub(crate) fn new() -> Self {
+        Path(Vec::new())
+    }
+
+    /// the index of `expr` if it is being printed
+    pub(crate) fn find(&self, expr: &Expr) -> Option<usize> {
+        let id = identity(expr)?;
+        self.0.iter().position(|&p| p == id)
+    }
+
+    pub(crate) fn enter(&mut self, expr: &Expr) {
+        // conses cannot contain themselves, but count in the indices
+        self.0.push(identity(expr).unwrap_or(0));
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.0.pop();
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    /// true if the objects inside are printed as `...`
+    pub(crate) fn is_full(&self) -> bool {
+        self.0.len() >= MAX_PRINT_DEPTH
+    }
+}
+
+fn identity(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Vector(v) => Some(Rc::as_ptr(v) as usize),
+        Expr::HashTable(h) => Some(Rc::as_ptr(h) as usize),
+        _ => None,
+    }
+}
+
+/// `prin1` of `expr` as a part of the objects in `path`
+pub(crate) fn prin1_in(expr: &Expr, path: &mut Path) -> String {
+    struct InPath<'a, 'b> {
+        expr: &'a Expr,
+        path: RefCell<&'b mut Path>,
+    }
+    impl<'a, 'b> fmt::Display for InPath<'a, 'b> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            print(self.expr, true, &mut self.path.borrow_mut(), f)
+        }
+    }
+    InPath {
+        expr,
+        path: RefCell::new(path),
+    }
+    .to_string()
+}
+
+fn print(expr: &Expr, escape: bool, path: &mut Path, f: &mut fmt::Formatter) -> fmt::Result {
+    if let Some(i) = path.find(expr) {
+        return write!(f, "#{}", i);
+    }
+    match expr {
+        Expr::Cons(_, _) | Expr::Vector(_) | Expr::HashTable(_) if path.is_full() => {
+            write!(f, "...")
+        }
+        Expr::Cons(_, _) | Expr::Vector(_) | Expr::HashTable(_) => {
+            path.enter(expr);
+            let ret = print_object(expr, escape, path, f);
+            path.leave();
+            ret
+        }
+        _ => print_object(expr, escape, path, f),
+    }
+}
+
+fn print_object(expr: &Expr, escape: bool, path: &mut Path, f: &mut fmt::Formatter) -> fmt::Result {
+    match expr {
+        Expr::Int(i) => write!(f, "{}", i),
+        Expr::Bignum(i) => write!(f, "{}", i),
+        Expr::Float(fl) => write!(f, "{}", format_float(*fl)),
+        Expr::Cons(car, cdr) => print_list(car, cdr, escape, path, f),
+        Expr::Nil => write!(f, "nil"),
+        Expr::Sym(s) if escape && !s.is_interned() => {
+            write!(f, "#:")?;
+            print_escaped_name(&s.name(), f)
+        }
+        Expr::Sym(s) if escape => print_symbol_name(&s.name(), f),
+        Expr::Sym(s) => write!(f, "{}", s),
+        Expr::Keyword(s) if escape => {
+            write!(f, ":")?;
+            print_escaped_name(s, f)
+        }
+        Expr::Keyword(s) => write!(f, ":{}", s),
+        Expr::Str(s) if escape => print_string(s, f),
+        Expr::Str(s) => write!(f, "{}", s),
+        Expr::Vector(v) => {
+            write!(f, "[")?;
+            for (i, e) in v.borrow().iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                print(e, escape, path, f)?;
+            }
+            write!(f, "]")
+        }
+        Expr::HashTable(h) => {
+            let h = h.borrow();
+            write!(f, "#s(hash-table")?;
+            if h.test() != HashTest::Eql {
+                write!(f, " test {}", h.test().name())?;
+            }
+            if !h.is_empty() {
+                write!(f, " data (")?;
+                for (i, (k, v)) in h.entries().iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    print(k, escape, path, f)?;
+                    write!(f, " ")?;
+                    print(v, escape, path, f)?;
+                }
+                write!(f, ")")?;
+            }
+            write!(f, ")")
+        }
+        Expr::Proc(p) => write!(f, "{}", p),
+    }
+}
+
+/// the reader macro that reads `(sym x)` from `prefix x`
+pub(crate) fn quote_prefix(sym: &symbol::Symbol) -> Option<&'static str> {
+    match *sym {
+        symbol::QUOTE => Some("'"),
+        symbol::FUNCTION => Some("#'"),
+        symbol::BACKQUOTE => Some("`"),
+        symbol::COMMA => Some(","),
+        symbol::COMMA_AT => Some(",@"),
+        _ => None,
+    }
+}
+
+fn print_list(
+    car: &Expr,
+    cdr: &Expr,
+    escape: bool,
+    path: &mut Path,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    // `(quote x)` and the like are printed as `'x`
+    if let (Expr::Sym(sym), Expr::Cons(arg, rest)) = (car, cdr) {
+        if let (Some(prefix), Expr::Nil) = (quote_prefix(sym), rest.deref()) {
+            write!(f, "{}", prefix)?;
+            // `,@x` would be read as `(,@ x)`
+            if let Expr::Sym(arg) = arg.deref() {
+                if *sym == symbol::COMMA && arg.name().starts_with('@') {
+                    write!(f, " ")?;
+                }
+            }
+            return print(arg, escape, path, f);
+        }
+    }
+    write!(f, "(")?;
+    print(car, escape, path, f)?;
+    let mut tmp = cdr;
+    loop {
+        match tmp {
+            Expr::Cons(car, cdr) => {
+                write!(f, " ")?;
+                print(car, escape, path, f)?;
+                tmp = cdr.deref()
+            }
+            Expr::Nil => break,
+            cdr => {
+                write!(f, " . ")?;
+                print(cdr, escape, path, f)?;
+                break;
+            }
+        }
+    }
+    write!(f, ")")
+}
+
+fn print_string(s: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    write!(f, "\"")
+}
+
+// escapes the name as a whole if the reader would take it for something else than a symbol
+fn print_symbol_name(name: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    if name.is_empty() {
+        return write!(f, "##");
+    }
+    if name == "." || name.starts_with(|c| "?#:".contains(c)) || is_number_syntax(name) {
+        write!(f, "\\")?;
+    }
+    print_escaped_name(name, f)
+}
+
+fn print_escaped_name(name: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    for c in name.chars() {
+        if c == '\\' || is_delimiter(c) {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    Ok(())
+}
+
+fn k_prin1_to_string(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (object, Any) & optional(noescape, Any));
+    let text = match noescape {
+        Some(Expr::Nil) | None => object.prin1().to_string(),
+        Some(_) => object.princ().to_string(),
+    };
+    env.reserve(text.len())?;
+    Ok(kstr(text))
+}
+
+fn format_error<S: Into<String>>(message: S) -> E {
+    E::Signal(Expr::Sym(symbol::ERROR), klist!(message.into()))
+}
+
+fn mismatch_error() -> E {
+    format_error("Format specifier doesn't match argument type")
+}
+
+// the largest width and precision of `format`, so that a directive cannot make a huge string
+const MAX_FORMAT_WIDTH: usize = 1 << 20;
+
+// a `%[field$][flags][width][.precision]conversion` of `format`
+#[derive(Default)]
+struct Spec {
+    /// 1-origin index of the argument given by `%N$`
+    field: Option<usize>,
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut n = None;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        n = Some(
+            n.unwrap_or(0usize)
+                .saturating_mul(10)
+                .saturating_add(d as usize),
+        );
+    }
+    n
+}
+
+// parses a directive after `%`
+fn parse_spec(chars: &mut Peekable<Chars>) -> Result<Spec> {
+    let mut spec = Spec::default();
+    let start = chars.clone();
+    match parse_number(chars) {
+        Some(field) if chars.peek() == Some(&'$') => {
+            chars.next();
+            spec.field = Some(field);
+        }
+        _ => *chars = start,
+    }
+    while let Some(&c) = chars.peek() {
+        match c {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            ' ' => spec.space = true,
+            '#' => spec.alternate = true,
+            _ => break,
+        }
+        chars.next();
+    }
+    spec.width = parse_number(chars).unwrap_or(0);
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        spec.precision = Some(parse_number(chars).unwrap_or(0));
+    }
+    match chars.next() {
+        Some(c) => spec.conversion = c,
+        None => {
+            return Err(format_error(
+                "Format string ends in middle of format specifier",
+            ))
+        }
+    }
+    Ok(spec)
+}
+
+impl Spec {
+    // the sign of a number to print
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    // pads `sign` followed by `body` to the width. `zero` allows padding with zeros after the sign.
+    fn pad(&self, sign: &str, body: &str, zero: bool, out: &mut String) {
+        let len = sign.chars().count() + body.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            out.push_str(sign);
+            out.push_str(body);
+            out.extend((0..fill).map(|_| ' '));
+        } else if zero && self.zero {
+            out.push_str(sign);
+            out.extend((0..fill).map(|_| '0'));
+            out.push_str(body);
+        } else {
+            out.extend((0..fill).map(|_| ' '));
+            out.push_str(sign);
+            out.push_str(body);
+        }
+    }
+
+    fn format_integer(&self, object: &Expr, out: &mut String) -> Result<()> {
+        let i = match object {
+            Expr::Int(i) => BigInt::from(*i),
+            Expr::Bignum(i) => i.deref().clone(),
+            Expr::Float(f) => BigInt::from_f64(f.trunc()).ok_or_else(mismatch_error)?,
+            _ => return Err(mismatch_error()),
+        };
+        let (radix, prefix) = match self.conversion {
+            'o' => (8, "0"),
+            'x' => (16, "0x"),
+            'X' => (16, "0X"),
+            _ => (10, ""),
+        };
+        let mut digits = i.abs().to_str_radix(radix);
+        if self.conversion == 'X' {
+            digits = digits.to_uppercase();
+        }
+        if let Some(precision) = self.precision {
+            if digits.len() < precision {
+                digits = "0".repeat(precision - digits.len()) + &digits;
+            }
+        }
+        // the prefix of `#` goes before the zeros of the padding
+        let mut sign = self.sign(i.is_negative()).to_string();
+        if self.alternate && !(prefix == "0" && digits.starts_with('0')) && !i.is_zero() {
+            sign.push_str(prefix);
+        }
+        self.pad(&sign, &digits, self.precision.is_none(), out);
+        Ok(())
+    }
+
+    fn format_float(&self, object: &Expr, out: &mut String) -> Result<()> {
+        let x = match object {
+            Expr::Int(i) => *i as Kfloat,
+            Expr::Bignum(i) => i.to_f64().unwrap_or(Kfloat::NAN),
+            Expr::Float(f) => *f,
+            _ => return Err(mismatch_error()),
+        };
+        let sign = self.sign(x.is_sign_negative() && !x.is_nan());
+        if !x.is_finite() {
+            let body = if x.is_nan() { "nan" } else { "inf" };
+            self.pad(sign, body, false, out);
+            return Ok(());
+        }
+        let precision = self.precision.unwrap_or(6);
+        let x = x.abs();
+        let mut body = match self.conversion {
+            'f' => format!("{:.*}", precision, x),
+            'e' => exponential(x, precision),
+            _ => {
+                // `%g` uses `%e` for large or small exponents and `%f` otherwise, with
+                // `precision` significant digits
+                let precision = ::std::cmp::max(precision, 1);
+                let e = exponential(x, precision - 1);
+                let exponent: i32 = e[e.find('e').unwrap() + 1..].parse().unwrap();
+                let mut body = if exponent < -4 || exponent >= precision as i32 {
+                    e
+                } else {
+                    format!("{:.*}", (precision as i32 - 1 - exponent) as usize, x)
+                };
+                if !self.alternate {
+                    body = strip_zeros(&body);
+                }
+                body
+            }
+        };
+        if self.alternate && !body.contains('.') {
+            let at = body.find('e').unwrap_or(body.len());
+            body.insert(at, '.');
+        }
+        self.pad(sign, &body, true, out);
+        Ok(())
+    }
+
+    fn format_char(&self, object: &Expr, out: &mut String) -> Result<()> {
+        let c = match object {
+            Expr::Int(c) if *c >= 0 => {
+                ::std::char::from_u32(*c as u32).ok_or_else(mismatch_error)?
+            }
+            _ => return Err(mismatch_error()),
+        };
+        self.pad("", &c.to_string(), false, out);
+        Ok(())
+    }
+
+    fn format_object(&self, object: &Expr, out: &mut String) -> Result<()> {
+        let text = match self.conversion {
+            'S' => object.prin1().to_string(),
+            _ => object.princ().to_string(),
+        };
+        let text: String = match self.precision {
+            Some(precision) => text.chars().take(precision).collect(),
+            None => text,
+        };
+        self.pad("", &text, false, out);
+        Ok(())
+    }
+}
+
+// `x` in the `%e` notation of C, such as `1.500000e+03`
+fn exponential(x: Kfloat, precision: usize) -> String {
+    let e = format!("{:.*e}", precision, x);
+    let (mantissa, exponent) = e.split_at(e.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let exp_sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, exp_sign, exponent.abs())
+}
+
+// removes the trailing zeros of the fraction for `%g`, and the point if nothing is left
+fn strip_zeros(s: &str) -> String {
+    let (mantissa, exponent) = s.split_at(s.find('e').unwrap_or(s.len()));
+    if !mantissa.contains('.') {
+        return s.to_string();
+    }
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+    format!("{}{}", mantissa, exponent)
+}
+
+/// formats `objects` by `string` like `format` of Emacs.
+/// `curve` turns the grave accents and apostrophes of `string` into curved quotes like `format-message`.
+/// The widths, the precisions and the result are charged to the allocation budget of `env`.
+pub fn format(env: &Env, string: &str, objects: &[&Expr], curve: bool) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = string.chars().peekable();
+    let mut next = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => (),
+            '`' if curve => {
+                out.push('\u{2018}');
+                continue;
+            }
+            '\'' if curve => {
+                out.push('\u{2019}');
+                continue;
+            }
+            c => {
+                out.push(c);
+                continue;
+            }
+        }
+        let spec = parse_spec(&mut chars)?;
+        let size = ::std::cmp::max(spec.width, spec.precision.unwrap_or(0));
+        if size > MAX_FORMAT_WIDTH {
+            return Err(format_error("Format width or precision too large"));
+        }
+        env.reserve(size)?;
+        if spec.conversion == '%' {
+            out.push('%');
+            continue;
+        }
+        let index = match spec.field {
+            Some(0) => return Err(format_error("Invalid format field number 0")),
+            Some(field) => field - 1,
+            None => next,
+        };
+        let object = match objects.get(index) {
+            Some(object) => object,
+            None => return Err(format_error("Not enough arguments for format string")),
+        };
+        next = index + 1;
+        match spec.conversion {
+            's' | 'S' => spec.format_object(object, &mut out)?,
+            'd' | 'o' | 'x' | 'X' => spec.format_integer(object, &mut out)?,
+            'e' | 'f' | 'g' => spec.format_float(object, &mut out)?,
+            'c' => spec.format_char(object, &mut out)?,
+            c => return Err(format_error(format!("Invalid format operation %{}", c))),
+        }
+    }
+    env.reserve(out.len())?;
+    Ok(out)
+}
+
+/// the format string and the arguments of `format` and the like
+pub(crate) fn format_args(args: &Expr) -> Result<(&str, Vec<&Expr>)> {
+    let (string, mut rest) = match args {
+        Expr::Cons(string, rest) => (get_args_one!(string.deref(), Str)?, rest.deref()),
+        Expr::Nil => return Err(E::ArityShort),
+        args => return Err(E::InvalidArgument(args.clone())),
+    };
+    let mut objects = Vec::new();
+    while let Expr::Cons(object, tail) = rest {
+        objects.push(object.deref());
+        rest = tail.deref();
+    }
+    match rest {
+        Expr::Nil => Ok((string, objects)),
+        _ => Err(E::InvalidArgument(args.clone())),
+    }
+}
+
+fn k_format(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (string, objects) = format_args(args)?;
+    Ok(kstr(format(env, string, &objects, false)?))
+}
+
+fn k_format_message(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let (string, objects) = format_args(args)?;
+    Ok(kstr(format(env, string, &objects, true)?))
+}
+
+pub fn init(env: &mut Env) -> Result<()> {
+    env.fregister(
+        "prin1-to-string",
+        kprim("k_prin1_to_string", k_prin1_to_string),
+    );
+    env.fregister("format", kprim("k_format", k_format));
+    env.fregister(
+        "format-message",
+        kprim("k_format_message", k_format_message),
+    );
+    Ok(())
+}
@@ -1,210 +1,1032 @@
+use std::fmt;
+use std::io::BufRead;
 use std::iter::Peekable;
 use std::ops::Deref;
+use std::result;
 use std::str::{Chars, FromStr};
 
+use num_bigint::BigInt;
+use num_traits::Num;
+
 use expr::{Error, Expr, Kfloat, Kint, Result};
+use hash;
+use symbol;
 use util::*;
 
-fn next_nonwhitespaces(input: &mut Peekable<Chars>, first: char) -> Option<char> {
-    match first.is_whitespace() {
-        false => return Some(first),
-        true => (),
-    }
-    while input.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
-        input.next();
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    /// byte offset from the start of the input
+    pub offset: usize,
+    /// 1-origin line number
+    pub line: usize,
+    /// 1-origin column number, counted in characters
+    pub column: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Reason {
+    UnexpectedEof,
+    UnterminatedString,
+    UnterminatedComment,
+    UnbalancedParen,
+    BadDot,
+    UnknownDispatch,
+    InvalidEscape,
+    InvalidChar,
+    InvalidNumber,
+    InvalidSyntax,
+    TooDeep,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReadError {
+    pub position: Position,
+    /// the offending character. `None` if the input ended.
+    pub found: Option<char>,
+    pub reason: Reason,
+}
+
+type ReadResult<T> = result::Result<T, ReadError>;
+
+impl ReadError {
+    /// true if the error is caused by the input ending in the middle of a datum
+    pub fn is_incomplete(&self) -> bool {
+        self.found.is_none()
     }
-    input.next()
 }
 
-fn peek_nonwhitespaces(input: &mut Peekable<Chars>, first: char) -> Option<char> {
-    match first.is_whitespace() {
-        false => return Some(first),
-        true => (),
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::UnexpectedEof => write!(f, "unexpected end of input"),
+            Reason::UnterminatedString => write!(f, "unterminated string"),
+            Reason::UnterminatedComment => write!(f, "unterminated comment"),
+            Reason::UnbalancedParen => write!(f, "unbalanced parenthesis"),
+            Reason::BadDot => write!(f, "bad dot syntax"),
+            Reason::UnknownDispatch => write!(f, "unknown dispatch character"),
+            Reason::InvalidEscape => write!(f, "invalid escape sequence"),
+            Reason::InvalidChar => write!(f, "invalid character literal"),
+            Reason::InvalidNumber => write!(f, "invalid number"),
+            Reason::InvalidSyntax => write!(f, "invalid syntax"),
+            Reason::TooDeep => write!(f, "too deeply nested"),
+        }
     }
-    while input.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
-        input.next();
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.reason, self.position.line, self.position.column
+        )?;
+        match self.found {
+            Some(c) => write!(f, " (found {:?})", c),
+            None => Ok(()),
+        }
     }
-    input.peek().map(|c| c.clone())
 }
 
-fn is_delimiter(c: char) -> bool {
-    c.is_whitespace() || "()\"'".contains(c)
+/// A character stream that keeps track of the position for error reporting
+#[derive(Clone)]
+pub struct Input<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: Position,
+    last: Position,
+    last_char: Option<char>,
+    // nesting of the data being read
+    depth: usize,
 }
 
-fn read_uint(input: &mut Peekable<Chars>, first: char, radix: u32) -> Option<Kint> {
-    let mut acc = String::new();
-    acc.push(first);
-    while input.peek().unwrap_or(&' ').is_digit(radix) {
-        let c = match input.next() {
-            Some(x) => x,
-            None => break,
-        };
-        acc.push(c);
+impl<'a> Input<'a> {
+    pub fn new(s: &'a str) -> Self {
+        Self::starting_at(
+            s,
+            Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+        )
+    }
+
+    /// an input whose first character is at `start`
+    pub fn starting_at(s: &'a str, start: Position) -> Self {
+        Input {
+            chars: s.chars().peekable(),
+            pos: start,
+            last: start,
+            last_char: None,
+            depth: 0,
+        }
+    }
+
+    /// position of the next character
+    pub fn position(&self) -> Position {
+        self.pos
+    }
+
+    // consumes `len` bytes of the input and returns the end position
+    fn skip(mut self, len: usize) -> Position {
+        let end = self.pos.offset + len;
+        while self.pos.offset < end && self.next().is_some() {}
+        self.pos
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+
+    // peeks the character after the next one
+    fn peek2(&self) -> Option<char> {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.last = self.pos;
+        self.last_char = Some(c);
+        self.pos.offset += c.len_utf8();
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, reason: Reason) -> ReadResult<char> {
+        match self.next() {
+            Some(c) => Ok(c),
+            None => Err(self.eof(reason)),
+        }
+    }
+
+    // an error at the end of the input
+    fn eof(&self, reason: Reason) -> ReadError {
+        ReadError {
+            position: self.pos,
+            found: None,
+            reason,
+        }
+    }
+
+    // an error at the last consumed character
+    fn error(&self, reason: Reason) -> ReadError {
+        ReadError {
+            position: self.last,
+            found: self.last_char,
+            reason,
+        }
     }
-    Some(Kint::from_str_radix(&acc[..], radix).unwrap())
 }
 
-fn read_int(input: &mut Peekable<Chars>, first: char, radix: u32) -> Option<Kint> {
-    match first {
-        '0'...'9' => read_uint(input, first, radix),
-        _ => {
-            let c = input.next()?;
-            match first {
-                '+' => read_uint(input, c, radix),
-                '-' => Some(-1 * read_uint(input, c, radix)?),
-                _ => None,
+fn skip_block_comment(input: &mut Input) -> ReadResult<()> {
+    let mut depth = 1;
+    while depth > 0 {
+        match input.expect(Reason::UnterminatedComment)? {
+            '|' if input.peek() == Some('#') => {
+                input.next();
+                depth -= 1;
             }
+            '#' if input.peek() == Some('|') => {
+                input.next();
+                depth += 1;
+            }
+            _ => (),
         }
     }
+    Ok(())
 }
 
-fn read_number(input: &mut Peekable<Chars>, first: char, radix: u32) -> Option<Expr> {
-    let i = read_int(input, first, radix)?;
-    match input.peek() {
-        Some(&'.') => {
-            let mut acc = String::new();
-            match first {
-                '-' => {
-                    acc.push('-');
-                    acc.push('0')
-                }
-                _ => acc.push('0'),
+// skips whitespaces, `;` line comments, `#|...|#` block comments and `#_`/`#;` datum comments
+fn skip_whitespaces(input: &mut Input) -> ReadResult<()> {
+    loop {
+        match input.peek() {
+            Some(c) if c.is_whitespace() => {
+                input.next();
             }
-            acc.push(input.next()?);
-            while input.peek().unwrap_or(&' ').is_digit(radix) {
-                let c = match input.next() {
-                    Some(x) => x,
-                    None => break,
-                };
-                acc.push(c);
+            Some(';') => while input.next().map(|c| c != '\n').unwrap_or(false) {},
+            Some('#') => match input.peek2() {
+                Some('|') => {
+                    input.next();
+                    input.next();
+                    skip_block_comment(input)?;
+                }
+                Some('_') | Some(';') => {
+                    input.next();
+                    input.next();
+                    read_aux(input, ' ')?;
+                }
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn next_nonwhitespaces(input: &mut Input, first: char) -> ReadResult<Option<char>> {
+    match first.is_whitespace() {
+        false => return Ok(Some(first)),
+        true => (),
+    }
+    skip_whitespaces(input)?;
+    Ok(input.next())
+}
+
+fn peek_nonwhitespaces(input: &mut Input) -> ReadResult<Option<char>> {
+    skip_whitespaces(input)?;
+    Ok(input.peek())
+}
+
+pub(crate) fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]\"';`,".contains(c)
+}
+
+fn read_token(input: &mut Input, first: char) -> String {
+    let mut token = first.to_string();
+    while input.peek().map(|c| !is_delimiter(c)).unwrap_or(false) {
+        token.push(input.next().unwrap());
+    }
+    token
+}
+
+// reads the name of a symbol. A `\\` makes the next character a part of the name,
+// and a name with escapes is never a number. Returns the name and if it had escapes.
+fn read_name(input: &mut Input, first: Option<char>) -> ReadResult<(String, bool)> {
+    let mut name = String::new();
+    let mut escaped = false;
+    let mut next = first;
+    loop {
+        match next {
+            Some('\\') => {
+                escaped = true;
+                name.push(input.expect(Reason::UnexpectedEof)?);
             }
-            // FIXME: ignoring radix
-            let f = Kfloat::from_str(&acc[..]).unwrap();
-            Some(Expr::Float((i as Kfloat) + f))
+            Some(c) => name.push(c),
+            None => (),
+        }
+        match input.peek() {
+            Some(c) if !is_delimiter(c) => next = input.next(),
+            _ => return Ok((name, escaped)),
+        }
+    }
+}
+
+fn split_sign(token: &str) -> (bool, &str) {
+    match token.chars().next() {
+        Some('-') => (true, &token[1..]),
+        Some('+') => (false, &token[1..]),
+        _ => (false, token),
+    }
+}
+
+fn count_digits(s: &str, radix: u32) -> usize {
+    s.chars().take_while(|c| c.is_digit(radix)).count()
+}
+
+/// true if `token` would be read as a number. The printer escapes such symbol names.
+pub(crate) fn is_number_syntax(token: &str) -> bool {
+    parse_decimal(token).is_some()
+}
+
+// parses `token` as a decimal integer or a float.
+// Returns `None` if `token` doesn't have a number syntax.
+fn parse_decimal(token: &str) -> Option<result::Result<Expr, Reason>> {
+    let (negative, rest) = split_sign(token);
+    let int_len = count_digits(rest, 10);
+    let mut rest = &rest[int_len..];
+    let mut frac_len = 0;
+    let has_dot = rest.starts_with('.');
+    if has_dot {
+        rest = &rest[1..];
+        frac_len = count_digits(rest, 10);
+        rest = &rest[frac_len..];
+    }
+    if int_len + frac_len == 0 {
+        return None;
+    }
+    let mantissa = &token[..token.len() - rest.len()];
+    if rest.is_empty() {
+        if frac_len == 0 {
+            // integer, possibly with a trailing dot
+            let digits = mantissa.trim_end_matches('.');
+            return Some(
+                Kint::from_str(digits)
+                    .map(kint)
+                    .or_else(|_| BigInt::from_str(digits).map(kbigint))
+                    .map_err(|_| Reason::InvalidNumber),
+            );
         }
-        _ => Some(Expr::Int(i)),
+        return Some(Kfloat::from_str(mantissa).map(kfloat).map_err(|_| Reason::InvalidNumber));
+    }
+    if !rest.starts_with('e') && !rest.starts_with('E') {
+        return None;
     }
+    let exponent = &rest[1..];
+    let special = match exponent {
+        "+INF" => Some(Kfloat::INFINITY),
+        "+NaN" => Some(Kfloat::NAN),
+        _ => None,
+    };
+    if let Some(f) = special {
+        return Some(Ok(kfloat(if negative { -f } else { f })));
+    }
+    let (_, exp_digits) = split_sign(exponent);
+    if exp_digits.is_empty() || count_digits(exp_digits, 10) != exp_digits.len() {
+        return None;
+    }
+    Some(Kfloat::from_str(token).map(kfloat).map_err(|_| Reason::InvalidNumber))
 }
 
-fn read_symbol(input: &mut Peekable<Chars>, first: char) -> Option<Expr> {
-    let mut sym = first.to_string();
-    while input.peek().map(|c| !is_delimiter(*c)).unwrap_or(false) {
-        sym.push(input.next().unwrap());
+fn read_symbol(input: &mut Input, first: char) -> ReadResult<Expr> {
+    let (sym, escaped) = read_name(input, Some(first))?;
+    if !escaped {
+        match parse_decimal(&sym) {
+            Some(Ok(n)) => return Ok(n),
+            Some(Err(reason)) => return Err(input.error(reason)),
+            None => (),
+        }
     }
     if sym == "nil" {
-        Some(knil())
+        Ok(knil())
     } else {
-        Some(ksym(sym))
+        Ok(ksym(sym))
     }
 }
 
-fn read_keyword(input: &mut Peekable<Chars>, first: char) -> Option<Expr> {
+// reads an integer written in `radix` after `#x`, `#o`, `#b` or `#NNr`
+fn read_radix_number(input: &mut Input, radix: u32) -> ReadResult<Expr> {
+    let token = match input.peek() {
+        Some(c) if !is_delimiter(c) => {
+            input.next();
+            read_token(input, c)
+        }
+        _ => String::new(),
+    };
+    let (negative, digits) = split_sign(&token);
+    if digits.is_empty() || count_digits(digits, radix) != digits.len() {
+        return Err(input.error(Reason::InvalidNumber));
+    }
+    match BigInt::from_str_radix(digits, radix) {
+        Ok(i) if negative => Ok(kbigint(-i)),
+        Ok(i) => Ok(kbigint(i)),
+        Err(_) => Err(input.error(Reason::InvalidNumber)),
+    }
+}
+
+fn read_keyword(input: &mut Input, first: char) -> ReadResult<Expr> {
     debug_assert_eq!(first, ':');
-    let mut kw = String::new();
-    while input.peek().map(|c| !is_delimiter(*c)).unwrap_or(false) {
-        kw.push(input.next().unwrap());
+    let (kw, _) = read_name(input, None)?;
+    Ok(kkw(kw))
+}
+
+// reads up to `max` digits of `radix` and returns the value and the number of digits consumed
+fn read_digits(input: &mut Input, radix: u32, max: usize) -> ReadResult<(u32, usize)> {
+    let mut acc: u32 = 0;
+    let mut n = 0;
+    while n < max {
+        let d = match input.peek().and_then(|c| c.to_digit(radix)) {
+            Some(d) => d,
+            None => break,
+        };
+        input.next();
+        acc = acc
+            .checked_mul(radix)
+            .and_then(|acc| acc.checked_add(d))
+            .ok_or_else(|| input.error(Reason::InvalidEscape))?;
+        n += 1;
     }
-    Some(kkw(kw))
+    Ok((acc, n))
 }
 
-fn read_plus(input: &mut Peekable<Chars>, first: char) -> Option<Expr> {
-    let c = input.peek()?.clone();
-    match c.is_digit(10) {
-        true => read_number(input, first, 10),
-        false => read_symbol(input, first),
+const ALT_MODIFIER: u32 = 0x0040_0000;
+const SUPER_MODIFIER: u32 = 0x0080_0000;
+const HYPER_MODIFIER: u32 = 0x0100_0000;
+const SHIFT_MODIFIER: u32 = 0x0200_0000;
+const CTRL_MODIFIER: u32 = 0x0400_0000;
+const META_MODIFIER: u32 = 0x0800_0000;
+const MODIFIER_MASK: u32 = 0x0fc0_0000;
+
+fn control_char(c: u32) -> u32 {
+    let base = c & !MODIFIER_MASK;
+    let modifiers = c & MODIFIER_MASK;
+    if base == '?' as u32 {
+        0o177 | modifiers
+    } else if base < 0o200 && (0o101..=0o132).contains(&(base & 0o137)) {
+        // letters of both cases
+        (base & 0o37) | modifiers
+    } else if (0o100..=0o137).contains(&base) {
+        (base & 0o37) | modifiers
+    } else {
+        c | CTRL_MODIFIER
     }
 }
 
-fn read_hyphen(input: &mut Peekable<Chars>, first: char) -> Option<Expr> {
-    let c = input.peek()?.clone();
-    match c.is_digit(10) {
-        true => read_number(input, first, 10),
-        false => read_symbol(input, first),
+fn read_modified(input: &mut Input, modifier: u32) -> ReadResult<u32> {
+    let c = match input.expect(Reason::UnexpectedEof)? {
+        '\\' => read_escape(input)?,
+        c => c as u32,
+    };
+    match modifier {
+        CTRL_MODIFIER => Ok(control_char(c)),
+        modifier => Ok(c | modifier),
     }
 }
 
-fn read_string(input: &mut Peekable<Chars>, _: char) -> Option<Expr> {
+// reads the escape sequence following a backslash and returns the character code,
+// possibly with modifier bits set
+fn read_escape(input: &mut Input) -> ReadResult<u32> {
+    let c = input.expect(Reason::UnexpectedEof)?;
+    let modifier = match c {
+        'C' => Some(CTRL_MODIFIER),
+        'M' => Some(META_MODIFIER),
+        'S' => Some(SHIFT_MODIFIER),
+        'H' => Some(HYPER_MODIFIER),
+        'A' => Some(ALT_MODIFIER),
+        's' => Some(SUPER_MODIFIER),
+        _ => None,
+    };
+    match modifier {
+        Some(modifier) if input.peek() == Some('-') => {
+            input.next();
+            return read_modified(input, modifier);
+        }
+        // `s` without a hyphen is a space
+        Some(_) if c != 's' => return Err(input.error(Reason::InvalidEscape)),
+        _ => (),
+    }
+    let code = match c {
+        '^' => return read_modified(input, CTRL_MODIFIER),
+        'a' => 7,
+        'b' => 8,
+        't' => 9,
+        'n' => 10,
+        'v' => 11,
+        'f' => 12,
+        'r' => 13,
+        'e' => 27,
+        's' => 32,
+        'd' => 127,
+        '0'..='7' => {
+            let (rest, n) = read_digits(input, 8, 2)?;
+            c.to_digit(8).unwrap() * 8u32.pow(n as u32) + rest
+        }
+        'x' => match read_digits(input, 16, usize::MAX)? {
+            (_, 0) => return Err(input.error(Reason::InvalidEscape)),
+            (code, _) => code,
+        },
+        'u' => match read_digits(input, 16, 4)? {
+            (code, 4) => code,
+            _ => return Err(input.error(Reason::InvalidEscape)),
+        },
+        'U' => match read_digits(input, 16, 8)? {
+            (code, 8) => code,
+            _ => return Err(input.error(Reason::InvalidEscape)),
+        },
+        c => c as u32,
+    };
+    match ::std::char::from_u32(code) {
+        Some(c) => Ok(c as u32),
+        None => Err(input.error(Reason::InvalidEscape)),
+    }
+}
+
+fn read_string(input: &mut Input, _: char) -> ReadResult<Expr> {
     let mut string = String::new();
-    // :TODO: treat escapes
     loop {
-        let c = input.next()?;
-        match c == '"' {
-            true => return Some(kstr(string)),
-            false => string.push(c),
+        let c = input.expect(Reason::UnterminatedString)?;
+        match c {
+            '"' => return Ok(kstr(string)),
+            '\\' => match input.peek() {
+                None => return Err(input.eof(Reason::UnterminatedString)),
+                // escaped newline and `\ ` are ignored
+                Some('\n') | Some(' ') => {
+                    input.next();
+                }
+                _ => match ::std::char::from_u32(read_escape(input)?) {
+                    Some(c) => string.push(c),
+                    None => return Err(input.error(Reason::InvalidEscape)),
+                },
+            },
+            c => string.push(c),
         }
     }
 }
 
-fn read_list(input: &mut Peekable<Chars>, _: char) -> Option<Expr> {
-    let c = next_nonwhitespaces(input, ' ')?;
-    let car = match c {
-        ')' => return Some(knil()),
-        _ => read_aux(input, c),
+fn read_char(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let c = match input.expect(Reason::UnexpectedEof)? {
+        '\\' => read_escape(input)?,
+        c => c as u32,
     };
+    match input.peek() {
+        Some(d) if !is_delimiter(d) => {
+            input.next();
+            Err(input.error(Reason::InvalidChar))
+        }
+        _ => Ok(kint(c as Kint)),
+    }
+}
 
-    let c = peek_nonwhitespaces(input, ' ')?;
-    let cdr = if c == '.' {
-        let _ = next_nonwhitespaces(input, ' ')?; // == 'c'
-        match read_list(input, '(')? {
-            Expr::Cons(ref e, ref nil) => {
-                if nil.deref() == &knil() {
-                    Some(e.deref().clone())
-                } else {
-                    None
+// a `.` followed by a delimiter
+fn is_dot(input: &Input, c: char) -> bool {
+    c == '.' && input.peek2().map(is_delimiter).unwrap_or(true)
+}
+
+fn read_list(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let mut elements = Vec::new();
+    let mut tail = knil();
+    loop {
+        match peek_nonwhitespaces(input)? {
+            None => return Err(input.eof(Reason::UnbalancedParen)),
+            Some(')') => {
+                input.next();
+                break;
+            }
+            Some(c) if is_dot(input, c) => {
+                input.next();
+                if elements.is_empty() {
+                    return Err(input.error(Reason::BadDot));
+                }
+                match peek_nonwhitespaces(input)? {
+                    Some(')') => {
+                        input.next();
+                        return Err(input.error(Reason::BadDot));
+                    }
+                    _ => tail = read_aux(input, ' ')?,
+                }
+                match next_nonwhitespaces(input, ' ')? {
+                    None => return Err(input.eof(Reason::UnbalancedParen)),
+                    Some(')') => break,
+                    Some(_) => return Err(input.error(Reason::BadDot)),
                 }
             }
-            _ => None,
+            Some(_) => elements.push(read_aux(input, ' ')?),
         }
-    } else {
-        read_list(input, '(')
-    };
-    match (car, cdr) {
-        (Some(car), Some(cdr)) => Some(kcons(car, cdr)),
-        _ => None,
     }
+    Ok(elements
+        .into_iter()
+        .rev()
+        .fold(tail, |acc, e| kcons(e, acc)))
+}
+
+fn read_vector(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let mut elements = Vec::new();
+    loop {
+        match peek_nonwhitespaces(input)? {
+            None => return Err(input.eof(Reason::UnbalancedParen)),
+            Some(']') => {
+                input.next();
+                return Ok(kvector(elements));
+            }
+            Some(_) => elements.push(read_aux(input, ' ')?),
+        }
+    }
+}
+
+fn read_quote(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let v = read_aux(input, ' ')?;
+    Ok(klist!(ksym("quote"), v))
 }
 
-fn read_quote(input: &mut Peekable<Chars>, _: char) -> Option<Expr> {
+fn read_backquote(input: &mut Input, _: char) -> ReadResult<Expr> {
     let v = read_aux(input, ' ')?;
-    Some(klist!(ksym("quote"), v))
+    Ok(klist!(ksym("`"), v))
 }
 
-fn read_function(input: &mut Peekable<Chars>, _: char) -> Option<Expr> {
+fn read_unquote(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let name = match input.peek() {
+        Some('@') => {
+            input.next();
+            ",@"
+        }
+        _ => ",",
+    };
+    let v = read_aux(input, ' ')?;
+    Ok(klist!(ksym(name), v))
+}
+
+fn read_function(input: &mut Input, _: char) -> ReadResult<Expr> {
     let v = read_aux(input, ' ')?;
-    Some(klist!(ksym("function"), v))
+    Ok(klist!(ksym("function"), v))
 }
 
-fn read_dispatch(input: &mut Peekable<Chars>, _: char) -> Option<Expr> {
-    let v = input.next()?;
+// reads `#s(hash-table ...)`. Other records are not supported.
+fn read_record(input: &mut Input) -> ReadResult<Expr> {
+    match input.expect(Reason::UnexpectedEof)? {
+        '(' => (),
+        _ => return Err(input.error(Reason::InvalidSyntax)),
+    }
+    match read_list(input, '(')? {
+        Expr::Cons(ref name, ref params) if name.deref() == &ksym("hash-table") => {
+            match hash::from_literal(params) {
+                Some(table) => Ok(khash_table(table)),
+                None => Err(input.error(Reason::InvalidSyntax)),
+            }
+        }
+        _ => Err(input.error(Reason::InvalidSyntax)),
+    }
+}
+
+fn read_dispatch(input: &mut Input, _: char) -> ReadResult<Expr> {
+    let v = input.expect(Reason::UnexpectedEof)?;
     match v {
         '\'' => read_function(input, '\''),
-        v => panic!("unknown reader macro #{:?}", v),
+        's' => read_record(input),
+        // `##` is the symbol with the empty name
+        '#' => Ok(ksym("")),
+        // `#:name` is a new uninterned symbol, never a number
+        ':' => {
+            let (name, _) = read_name(input, None)?;
+            Ok(Expr::Sym(symbol::make_symbol(&name)))
+        }
+        'x' | 'X' => read_radix_number(input, 16),
+        'o' | 'O' => read_radix_number(input, 8),
+        'b' | 'B' => read_radix_number(input, 2),
+        '0'..='9' => {
+            let (rest, n) = read_digits(input, 10, 1)?;
+            let radix = v.to_digit(10).unwrap() * 10u32.pow(n as u32) + rest;
+            match input.expect(Reason::UnexpectedEof)? {
+                'r' | 'R' if (2..=36).contains(&radix) => read_radix_number(input, radix),
+                'r' | 'R' => Err(input.error(Reason::InvalidNumber)),
+                _ => Err(input.error(Reason::UnknownDispatch)),
+            }
+        }
+        _ => Err(input.error(Reason::UnknownDispatch)),
     }
 }
 
-fn read_aux(input: &mut Peekable<Chars>, first: char) -> Option<Expr> {
-    let first = next_nonwhitespaces(input, first)?;
+// nesting of data over which the reader gives up, as it would overflow the stack
+const MAX_READ_DEPTH: usize = 1000;
+
+fn read_aux(input: &mut Input, first: char) -> ReadResult<Expr> {
+    if input.depth >= MAX_READ_DEPTH {
+        return Err(input.error(Reason::TooDeep));
+    }
+    input.depth += 1;
+    let res = read_datum(input, first);
+    input.depth -= 1;
+    res
+}
+
+fn read_datum(input: &mut Input, first: char) -> ReadResult<Expr> {
+    let first = match next_nonwhitespaces(input, first)? {
+        Some(c) => c,
+        None => return Err(input.eof(Reason::UnexpectedEof)),
+    };
     match first {
-        '0'...'9' => read_number(input, first, 10),
-        '-' => read_hyphen(input, first),
-        '+' => read_plus(input, first),
         '(' => read_list(input, first),
+        ')' | ']' => Err(input.error(Reason::UnbalancedParen)),
+        '[' => read_vector(input, first),
         '"' => read_string(input, first),
+        '?' => read_char(input, first),
         '\'' => read_quote(input, first),
+        '`' => read_backquote(input, first),
+        ',' => read_unquote(input, first),
         '#' => read_dispatch(input, first),
         ':' => read_keyword(input, first),
+        '.' if input.peek().map(is_delimiter).unwrap_or(true) => {
+            Err(input.error(Reason::BadDot))
+        }
         _ => read_symbol(input, first),
     }
 }
 
-pub fn read_in(input: &mut Peekable<Chars>) -> Option<Expr> {
-    read_aux(input, ' ')
+/// reads the next datum. Returns `None` if only whitespaces and comments are left.
+pub fn read_in(input: &mut Input) -> Result<Option<Expr>> {
+    skip_whitespaces(input)?;
+    match input.peek() {
+        None => Ok(None),
+        Some(_) => Ok(Some(read_aux(input, ' ')?)),
+    }
 }
 
+/// reads the first datum of `s`. The rest of the input is ignored.
 pub fn read(s: &str) -> Result<Expr> {
-    let mut input = s.chars().peekable();
-    match read_aux(&mut input, ' ') {
-        Some(e) => Ok(e),
-        None => Err(Error::ReadError),
+    let mut input = Input::new(s);
+    read_aux(&mut input, ' ').map_err(Error::Read)
+}
+
+/// Result of reading from a partially received input
+#[derive(Debug, PartialEq)]
+pub enum Parsed {
+    Datum(Expr),
+    /// the input ends in the middle of a datum
+    Incomplete,
+    /// nothing but whitespaces and comments are left
+    Empty,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ScanState {
+    Normal,
+    // after a `\\` in a symbol name
+    Escape,
+    String,
+    StringEscape,
+    LineComment,
+    // after a `#`, which may start a block comment
+    Hash,
+    // in `#|...|#` of the depth, after `|` or `#` that may close or open one
+    BlockComment(usize),
+    BlockBar(usize),
+    BlockHash(usize),
+    // after `?` or a modifier like `\\C-`: the next character is a part of the literal
+    Char,
+    CharEscape,
+    // after `\\C` and the like, which is a modifier if `-` follows
+    CharModifier,
+}
+
+// follows the nesting of lists, strings and comments over the chunks fed to a `ReadBuffer`
+// without parsing them, so that the buffer only parses when a datum may be complete
+#[derive(Debug, Clone, Copy)]
+struct Scanner {
+    depth: usize,
+    state: ScanState,
+    // the last character was a delimiter, so `?` starts a character literal
+    token_start: bool,
+    // in an atom, which ends at the next delimiter
+    in_atom: bool,
+    // `#;` and `#_` comments at the top level whose datum has not ended yet.
+    // Ending data may be over-counted but never missed, so that the buffer never waits forever.
+    skips: usize,
+}
+
+impl Scanner {
+    fn new() -> Self {
+        Scanner {
+            depth: 0,
+            state: ScanState::Normal,
+            token_start: true,
+            in_atom: false,
+            skips: 0,
+        }
+    }
+
+    // true if a top-level datum may end here
+    fn at_top_level(&self) -> bool {
+        self.depth == 0
+            && self.skips == 0
+            && (self.state == ScanState::Normal || self.state == ScanState::LineComment)
+    }
+
+    // a datum has ended, which is the one a top-level datum comment skips if any
+    fn end_datum(&mut self) {
+        if self.depth == 0 {
+            self.skips = self.skips.saturating_sub(1);
+        }
+    }
+
+    fn feed(&mut self, c: char) {
+        use self::ScanState::*;
+        let token_start = self.token_start;
+        self.token_start = false;
+        self.state = match self.state {
+            Escape => Normal,
+            StringEscape => String,
+            String => match c {
+                '\\' => StringEscape,
+                '"' => {
+                    self.end_datum();
+                    Normal
+                }
+                _ => String,
+            },
+            LineComment if c == '\n' => {
+                self.token_start = true;
+                Normal
+            }
+            LineComment => LineComment,
+            BlockComment(n) => match c {
+                '|' => BlockBar(n),
+                '#' => BlockHash(n),
+                _ => BlockComment(n),
+            },
+            BlockBar(1) if c == '#' => Normal,
+            BlockBar(n) if c == '#' => BlockComment(n - 1),
+            BlockHash(n) if c == '|' => BlockComment(n + 1),
+            BlockBar(n) | BlockHash(n) if c == '|' => BlockBar(n),
+            BlockBar(n) | BlockHash(n) if c == '#' => BlockHash(n),
+            BlockBar(n) | BlockHash(n) => BlockComment(n),
+            Hash if c == '|' => {
+                self.in_atom = false;
+                BlockComment(1)
+            }
+            // `#;` and `#_` skip the next datum
+            Hash if c == ';' || c == '_' => {
+                self.in_atom = false;
+                self.token_start = true;
+                if self.depth == 0 {
+                    self.skips += 1;
+                }
+                Normal
+            }
+            Char if c == '\\' => CharEscape,
+            CharEscape if "CMSHsA".contains(c) => CharModifier,
+            CharEscape if c == '^' => Char,
+            Char | CharEscape => Normal,
+            CharModifier if c == '-' => Char,
+            Hash | CharModifier | Normal => {
+                self.state = Normal;
+                return self.feed_normal(c, token_start);
+            }
+        };
+    }
+
+    fn feed_normal(&mut self, c: char, token_start: bool) {
+        use self::ScanState::*;
+        self.token_start = is_delimiter(c);
+        if self.token_start && self.in_atom {
+            self.in_atom = false;
+            self.end_datum();
+        }
+        match c {
+            '(' | '[' => self.depth += 1,
+            ')' | ']' if self.depth > 0 => {
+                self.depth -= 1;
+                self.end_datum();
+            }
+            '"' => self.state = String,
+            ';' => self.state = LineComment,
+            c if is_delimiter(c) => (),
+            '\\' => {
+                self.in_atom = true;
+                self.state = Escape;
+            }
+            '#' => {
+                self.in_atom = true;
+                self.state = Hash;
+            }
+            '?' if token_start => {
+                self.in_atom = true;
+                self.state = Char;
+            }
+            _ => self.in_atom = true,
+        }
+    }
+}
+
+/// A buffer that is fed with text chunks and yields data as soon as they are complete
+pub struct ReadBuffer {
+    buffer: String,
+    position: Position,
+    finished: bool,
+    // the state at the end of the part of `buffer` scanned so far
+    scanner: Scanner,
+    scanned: usize,
+}
+
+impl ReadBuffer {
+    pub fn new() -> Self {
+        ReadBuffer {
+            buffer: String::new(),
+            position: Input::new("").position(),
+            finished: false,
+            scanner: Scanner::new(),
+            scanned: 0,
+        }
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.buffer.push_str(s);
+    }
+
+    /// tells that no more input will come, so that the pending text is read as is
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// position of the first unconsumed character in the whole stream
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn read_next(&mut self) -> Result<Parsed> {
+        // each character is scanned once, and parsing waits until a datum may be complete,
+        // so that a large datum arriving in many chunks is not parsed again and again
+        for c in self.buffer[self.scanned..].chars() {
+            self.scanner.feed(c);
+        }
+        self.scanned = self.buffer.len();
+        if !self.finished && !self.scanner.at_top_level() {
+            return Ok(Parsed::Incomplete);
+        }
+        let (res, end) = {
+            let mut input = Input::starting_at(&self.buffer, self.position);
+            let res = read_in(&mut input);
+            (res, input.position())
+        };
+        let consumed = end.offset - self.position.offset;
+        match res {
+            Ok(None) => {
+                self.consume(consumed, end);
+                Ok(Parsed::Empty)
+            }
+            Ok(Some(e)) => {
+                // an atom at the end of the buffer may continue in the next chunk
+                let last = self.buffer[..consumed].chars().next_back();
+                let at_end = consumed == self.buffer.len();
+                if at_end && !self.finished && !last.map(|c| ")]\"".contains(c)).unwrap_or(false) {
+                    return Ok(Parsed::Incomplete);
+                }
+                self.consume(consumed, end);
+                Ok(Parsed::Datum(e))
+            }
+            Err(Error::Read(ref e)) if e.is_incomplete() && !self.finished => Ok(Parsed::Incomplete),
+            Err(Error::Read(e)) => {
+                let len = self.broken_len(&e);
+                let end = Input::starting_at(&self.buffer, self.position).skip(len);
+                self.consume(len, end);
+                Err(Error::Read(e))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // the length of the broken datum that caused `e`: up to the end of the top-level form
+    // if the error is in a list, and up to the next delimiter otherwise
+    fn broken_len(&self, e: &ReadError) -> usize {
+        let found = match e.found {
+            Some(c) => c,
+            None => return self.buffer.len(),
+        };
+        let error_end = e.position.offset - self.position.offset + found.len_utf8();
+        let mut scanner = Scanner::new();
+        for c in self.buffer[..error_end].chars() {
+            scanner.feed(c);
+        }
+        let mut len = error_end;
+        for c in self.buffer[error_end..].chars() {
+            if scanner.at_top_level() && (is_delimiter(c) || scanner.token_start) {
+                break;
+            }
+            scanner.feed(c);
+            len += c.len_utf8();
+        }
+        len
+    }
+
+    fn consume(&mut self, len: usize, end: Position) {
+        self.buffer.drain(..len);
+        self.scanned -= len;
+        self.position = end;
+    }
+}
+
+impl Default for ReadBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads successive top-level data from a `BufRead`
+pub struct Reader<R> {
+    input: R,
+    buffer: ReadBuffer,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Reader {
+            input,
+            buffer: ReadBuffer::new(),
+        }
+    }
+
+    // reads a line into the buffer
+    fn fill(&mut self) -> Result<()> {
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) => self.buffer.finish(),
+            Ok(_) => self.buffer.push_str(&line),
+            Err(e) => return Err(Error::Io(e.to_string())),
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Expr>;
+
+    fn next(&mut self) -> Option<Result<Expr>> {
+        loop {
+            match self.buffer.read_next() {
+                Ok(Parsed::Datum(e)) => return Some(Ok(e)),
+                Ok(Parsed::Empty) if self.buffer.is_finished() => return None,
+                Ok(_) => match self.fill() {
+                    Ok(()) => (),
+                    Err(e) => return Some(Err(e)),
+                },
+                Err(e) => return Some(Err(e)),
+            }
+        }
     }
 }
@@ -0,0 +1,155 @@
+use std::mem::size_of;
+use std::ops::Deref;
+
+use env::Env;
+use expr::{Error as E, Expr, Kint, Result, Type};
+use util::*;
+
+// collects the elements of a list, a vector or a string
+fn seq_to_vec(seq: &Expr) -> Result<Vec<Expr>> {
+    match seq {
+        Expr::Nil | Expr::Cons(_, _) => {
+            let mut elements = Vec::new();
+            let mut head = seq;
+            while let Expr::Cons(car, cdr) = head {
+                elements.push(car.deref().clone());
+                head = cdr.deref();
+            }
+            match head {
+                Expr::Nil => Ok(elements),
+                _ => Err(E::Type(Type::Sequence, seq.clone())),
+            }
+        }
+        Expr::Vector(v) => Ok(v.borrow().clone()),
+        Expr::Str(s) => Ok(s.chars().map(|c| kint(c as Kint)).collect()),
+        seq => Err(E::Type(Type::Sequence, seq.clone())),
+    }
+}
+
+fn index(args: &Expr, i: Kint, len: usize) -> Result<usize> {
+    if 0 <= i && (i as usize) < len {
+        Ok(i as usize)
+    } else {
+        Err(E::InvalidArgument(args.clone()))
+    }
+}
+
+// the number of elements of a list, a vector or a string, without copying them
+fn seq_len(seq: &Expr) -> Result<usize> {
+    match seq {
+        Expr::Nil | Expr::Cons(_, _) => {
+            let mut len = 0;
+            let mut head = seq;
+            while let Expr::Cons(_, cdr) = head {
+                len += 1;
+                head = cdr.deref();
+            }
+            match head {
+                Expr::Nil => Ok(len),
+                _ => Err(E::Type(Type::Sequence, seq.clone())),
+            }
+        }
+        Expr::Vector(v) => Ok(v.borrow().len()),
+        Expr::Str(s) => Ok(s.chars().count()),
+        seq => Err(E::Type(Type::Sequence, seq.clone())),
+    }
+}
+
+// fails if a vector of `len` elements would exceed the allocation budget
+fn reserve_vector(env: &Env, len: usize) -> Result<()> {
+    env.reserve(len.saturating_mul(size_of::<Expr>()))
+}
+
+pub fn k_vector(env: &mut Env, args: &Expr) -> Result<Expr> {
+    reserve_vector(env, seq_len(args)?)?;
+    Ok(kvector(seq_to_vec(args)?))
+}
+
+pub fn k_make_vector(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (len, Int)(init, Any));
+    if len < 0 {
+        return Err(E::InvalidArgument(args.clone()));
+    }
+    reserve_vector(env, len as usize)?;
+    Ok(kvector(vec![init.clone(); len as usize]))
+}
+
+pub fn k_aref(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (array, Any)(i, Int));
+    match array {
+        Expr::Vector(v) => {
+            let v = v.borrow();
+            let i = index(args, i, v.len())?;
+            Ok(v[i].clone())
+        }
+        Expr::Str(s) => {
+            let i = index(args, i, s.chars().count())?;
+            Ok(kint(s.chars().nth(i).unwrap() as Kint))
+        }
+        array => Err(E::Type(Type::Array, array.clone())),
+    }
+}
+
+pub fn k_aset(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (v, Vector)(i, Int)(value, Any));
+    let mut v = v.borrow_mut();
+    let i = index(args, i, v.len())?;
+    v[i] = value.clone();
+    Ok(value.clone())
+}
+
+pub fn k_vconcat(env: &mut Env, args: &Expr) -> Result<Expr> {
+    let seqs = seq_to_vec(args)?;
+    let mut len = 0usize;
+    for seq in &seqs {
+        len = len.saturating_add(seq_len(seq)?);
+    }
+    reserve_vector(env, len)?;
+    let mut elements = Vec::with_capacity(len);
+    for seq in seqs {
+        elements.extend(seq_to_vec(&seq)?);
+    }
+    Ok(kvector(elements))
+}
+
+pub fn k_length(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (seq, Any));
+    Ok(kint(seq_len(seq)? as Kint))
+}
+
+pub fn k_elt(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (seq, Any)(i, Int));
+    match seq {
+        // like `nth`, out of range indices on lists give nil
+        Expr::Nil | Expr::Cons(_, _) => {
+            let mut head = seq;
+            for _ in 0..i {
+                match head {
+                    Expr::Cons(_, cdr) => head = cdr.deref(),
+                    _ => break,
+                }
+            }
+            match head {
+                Expr::Cons(car, _) => Ok(car.deref().clone()),
+                Expr::Nil => Ok(knil()),
+                _ => Err(E::Type(Type::Sequence, seq.clone())),
+            }
+        }
+        _ => {
+            let elements = seq_to_vec(seq)?;
+            let i = index(args, i, elements.len())?;
+            Ok(elements[i].clone())
+        }
+    }
+}
+
+pub fn init(env: &mut Env) -> Result<()> {
+    env.fregister("vector", kprim("k_vector", k_vector));
+    env.fregister("make-vector", kprim("k_make_vector", k_make_vector));
+    env.fregister("aref", kprim("k_aref", k_aref));
+    env.fregister("aset", kprim("k_aset", k_aset));
+    env.fregister("vconcat", kprim("k_vconcat", k_vconcat));
+    env.fregister("length", kprim("k_length", k_length));
+    env.fregister("elt", kprim("k_elt", k_elt));
+    Ok(())
+}
@@ -1,3 +1,8 @@
+;;; skk.lisp --- SKK helper functions loaded by `skk::init'
+
+;; bound by the caller around the evaluation of numeric candidates
+(defvar skk-num-list)
+
 (defun skk-gadget-units-conversion (base v target)
   (* v (cdr (assoc target (cdr (assoc base skk-units-alist))))))
 
@@ -2,25 +2,26 @@ extern crate time;
 
 use std::ops::Deref;
 
+use base::{k_add, k_div, k_mul, k_sub};
 use datetime::datetime_info_to_timespec;
 use env::Env;
 use eval::{eval, funcall};
 use expr::{Error as E, Expr, Result, Type};
-use read::read_in;
+use read::{read_in, Input};
 use util::*;
 
 pub fn k_skk_calc(env: &mut Env, args: &Expr) -> Result<Expr> {
     get_args!(args, (op, Sym));
-    let skk_num_list = env.find(&"skk-num-list".to_owned())?.clone();
-    get_args!(&skk_num_list, (x, Int)(y, Int));
-    let res = match &op[..] {
-        "+" => x + y,
-        "-" => x - y,
-        "*" => x * y,
-        "/" => x / y,
-        op => return Err(E::User(format!("unknown operator {}", op))),
-    };
-    Ok(kint(res))
+    let skk_num_list = env.find("skk-num-list")?;
+    get_args!(&skk_num_list, (_, Any)(_, Any));
+    // large numbers become bignums rather than overflowing
+    match &*op.name() {
+        "+" => k_add(env, &skk_num_list),
+        "-" => k_sub(env, &skk_num_list),
+        "*" => k_mul(env, &skk_num_list),
+        "/" => k_div(env, &skk_num_list),
+        op => Err(E::User(format!("unknown operator {}", op))),
+    }
 }
 
 pub fn k_skk_current_date_1(_: &mut Env, args: &Expr) -> Result<Expr> {
@@ -95,8 +96,8 @@ pub fn init(mut env: &mut Env) -> Result<()> {
         kprim("k_skk_current_date", k_skk_current_date),
     );
     let lisp = include_str!("skk.lisp");
-    let mut input = lisp.chars().peekable();
-    while let Some(e) = read_in(&mut input) {
+    let mut input = Input::new(lisp);
+    while let Some(e) = read_in(&mut input)? {
         let _ = eval(&mut env, &e)?;
     }
 
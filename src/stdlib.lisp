@@ -1,20 +1,66 @@
+;;; stdlib.lisp --- basic functions and macros loaded by `stdlib::init'
+
 (fset 'list (lambda (&rest args) args))
 (fset 'defmacro (cons 'macro (lambda (name params &rest body)
-                               (list 'fset (list 'quote name)
-                                     (list 'cons (list 'quote 'macro) (cons 'lambda (cons params body)))))))
+                               `(fset ',name (cons 'macro (lambda ,params ,@body))))))
+
+;; Definition forms
+
 (defmacro defun (name params &rest body)
-  (list 'fset (list 'quote name)
-        (cons 'lambda (cons params body))))
+  `(fset ',name (lambda ,params ,@body)))
 
 (defmacro setq (name val)
-  (list 'set (list 'quote name) val))
+  `(set ',name ,val))
 
 (defmacro defparameter (name val)
-  (list 'setq name val))
+  `(progn (defvar ,name) (setq ,name ,val)))
+
+;; Conditionals
+
+(defun not (x)
+  (if x nil t))
+
+(defun null (x)
+  (if x nil t))
+
+(defmacro when (cnd &rest body)
+  `(if ,cnd (progn ,@body)))
+
+(defmacro unless (cnd &rest body)
+  `(if ,cnd nil (progn ,@body)))
+
+;; Errors
+
+(defmacro ignore-errors (&rest body)
+  `(condition-case nil (progn ,@body) (error nil)))
+
+;; Loops
+
+;; (dolist (VAR LIST [RESULT]) BODY...)
+(defmacro dolist (spec &rest body)
+  `(let ((--dolist-tail-- ,(car (cdr spec)))
+         ,(car spec))
+     (while --dolist-tail--
+       (setq ,(car spec) (car --dolist-tail--))
+       ,@body
+       (setq --dolist-tail-- (cdr --dolist-tail--)))
+     ,@(cdr (cdr spec))))
+
+;; (dotimes (VAR COUNT [RESULT]) BODY...)
+(defmacro dotimes (spec &rest body)
+  `(let ((--dotimes-limit-- ,(car (cdr spec)))
+         (,(car spec) 0))
+     (while (< ,(car spec) --dotimes-limit--)
+       ,@body
+       (setq ,(car spec) (+ ,(car spec) 1)))
+     ,@(cdr (cdr spec))))
+
+;; Lists
 
 (defun assoc (key list)
-  (if list
-      (if (equalp key (car (car list)))
-          (car list)
-          (assoc key (cdr list)))
-      nil))
+  (let ((found nil))
+    (while (and list (not found))
+      (when (equalp key (car (car list)))
+        (setq found (car list)))
+      (setq list (cdr list)))
+    found))
@@ -1,12 +1,12 @@
 use env::Env;
 use eval::eval;
 use expr::Result;
-use read::read_in;
+use read::{read_in, Input};
 
 pub fn init(env: &mut Env) -> Result<()> {
     let lisp = include_str!("stdlib.lisp");
-    let mut input = lisp.chars().peekable();
-    while let Some(e) = read_in(&mut input) {
+    let mut input = Input::new(lisp);
+    while let Some(e) = read_in(&mut input)? {
         let _ = eval(env, &e)?;
     }
     Ok(())
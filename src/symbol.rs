@@ -0,0 +1,407 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::mem::size_of;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use env::Env;
+use expr::{Error as E, Expr, Proc, Result, Type};
+use util::*;
+
+/// a symbol. Symbols interned with the same name are the same `Symbol`, so comparing
+/// and hashing them doesn't look at their names.
+/// Uninterned symbols hold their slot in the obarray, which is reused once they are all dropped.
+#[derive(PartialEq, Eq, Clone)]
+pub struct Symbol(u32, Option<Rc<Slot>>);
+
+// the slot of an uninterned symbol. It doesn't take part in the comparison of symbols.
+struct Slot(u32);
+
+impl PartialEq for Slot {
+    fn eq(&self, _: &Slot) -> bool {
+        true
+    }
+}
+
+impl Eq for Slot {}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        // the obarray may be gone or busy while the thread exits
+        let _ = OBARRAY.try_with(|obarray| {
+            if let Ok(mut obarray) = obarray.try_borrow_mut() {
+                obarray.names[self.0 as usize] = Rc::from("");
+                obarray.free.push(self.0);
+            }
+        });
+    }
+}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// a `HashMap` keyed by symbols. It hashes the ids with a multiplication instead of SipHash,
+/// as variable lookups hash symbols all the time.
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+pub type SymbolSet = HashSet<Symbol, BuildHasherDefault<SymbolHasher>>;
+
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(u64::from(*byte));
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+// the names of the symbols and the obarray that maps names to the interned ones.
+// `Expr` is bound to a thread, so the interpreters of a thread share the table.
+// Interning counts as allocation, so that evaluations within limits cannot grow it without bound.
+struct Obarray {
+    names: Vec<Rc<str>>,
+    interned: HashMap<Rc<str>, Symbol>,
+    // the slots of the uninterned symbols that have been dropped
+    free: Vec<u32>,
+    gensym_counter: usize,
+}
+
+impl Obarray {
+    fn new() -> Obarray {
+        let mut obarray = Obarray {
+            names: Vec::new(),
+            interned: HashMap::new(),
+            free: Vec::new(),
+            gensym_counter: 0,
+        };
+        for name in WELL_KNOWN {
+            obarray.intern(name);
+        }
+        obarray
+    }
+
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(sym) = self.interned.get(name) {
+            return sym.clone();
+        }
+        let sym = Symbol(self.new_slot(name), None);
+        self.interned
+            .insert(self.names[sym.0 as usize].clone(), sym.clone());
+        sym
+    }
+
+    fn make_symbol(&mut self, name: &str) -> Symbol {
+        let slot = self.new_slot(name);
+        Symbol(slot, Some(Rc::new(Slot(slot))))
+    }
+
+    fn new_slot(&mut self, name: &str) -> u32 {
+        count_allocation(name.len() + size_of::<Rc<str>>());
+        match self.free.pop() {
+            Some(slot) => {
+                self.names[slot as usize] = Rc::from(name);
+                slot
+            }
+            None => {
+                self.names.push(Rc::from(name));
+                (self.names.len() - 1) as u32
+            }
+        }
+    }
+}
+
+thread_local!(static OBARRAY: RefCell<Obarray> = RefCell::new(Obarray::new()));
+
+macro_rules! well_known {
+    ($($id:ident = $name:expr,)*) => {
+        const WELL_KNOWN: &[&str] = &[$($name,)*];
+        well_known!(@consts 0, $($id,)*);
+    };
+    (@consts $n:expr, $id:ident, $($rest:ident,)*) => {
+        pub const $id: Symbol = Symbol($n, None);
+        well_known!(@consts $n + 1, $($rest,)*);
+    };
+    (@consts $n:expr,) => {};
+}
+
+// symbols the interpreter itself looks for. They are interned first in this order.
+well_known! {
+    T = "t",
+    QUOTE = "quote",
+    BACKQUOTE = "`",
+    COMMA = ",",
+    COMMA_AT = ",@",
+    FUNCTION = "function",
+    LAMBDA = "lambda",
+    MACRO = "macro",
+    PROGN = "progn",
+    IF = "if",
+    COND = "cond",
+    AND = "and",
+    OR = "or",
+    LET = "let",
+    LET_STAR = "let*",
+    FLET = "flet",
+    CL_FLET = "cl-flet",
+    LABELS = "labels",
+    CL_LABELS = "cl-labels",
+    FSET = "fset",
+    SET = "set",
+    DEFVAR = "defvar",
+    DEFCONST = "defconst",
+    WHILE = "while",
+    CATCH = "catch",
+    CONDITION_CASE = "condition-case",
+    UNWIND_PROTECT = "unwind-protect",
+    OPTIONAL = "&optional",
+    REST = "&rest",
+    ERROR = "error",
+}
+
+/// returns the symbol named `name` in the obarray, adding it if there is none
+pub fn intern(name: &str) -> Symbol {
+    OBARRAY.with(|obarray| obarray.borrow_mut().intern(name))
+}
+
+/// returns the symbol named `name` if it is interned
+pub fn intern_soft(name: &str) -> Option<Symbol> {
+    OBARRAY.with(|obarray| obarray.borrow().interned.get(name).cloned())
+}
+
+/// returns a new uninterned symbol. It is distinct from any other symbol even if it has the same name.
+pub fn make_symbol(name: &str) -> Symbol {
+    OBARRAY.with(|obarray| obarray.borrow_mut().make_symbol(name))
+}
+
+/// returns a new uninterned symbol named `prefix` followed by a counter
+pub fn gensym(prefix: &str) -> Symbol {
+    OBARRAY.with(|obarray| {
+        let mut obarray = obarray.borrow_mut();
+        let name = format!("{}{}", prefix, obarray.gensym_counter);
+        obarray.gensym_counter += 1;
+        obarray.make_symbol(&name)
+    })
+}
+
+impl Symbol {
+    pub fn name(&self) -> Rc<str> {
+        OBARRAY.with(|obarray| obarray.borrow().names[self.0 as usize].clone())
+    }
+
+    pub fn is_interned(&self) -> bool {
+        self.1.is_none()
+    }
+}
+
+impl<'a> From<&'a Symbol> for Symbol {
+    fn from(sym: &'a Symbol) -> Symbol {
+        sym.clone()
+    }
+}
+
+impl<'a> From<&'a str> for Symbol {
+    fn from(name: &'a str) -> Symbol {
+        intern(name)
+    }
+}
+
+impl<'a> From<&'a String> for Symbol {
+    fn from(name: &'a String) -> Symbol {
+        intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Symbol {
+        intern(&name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.name())
+    }
+}
+
+// `nil` is read as `Expr::Nil` rather than a symbol
+fn symbol_expr(name: &str, sym: Symbol) -> Expr {
+    if name == "nil" {
+        knil()
+    } else {
+        Expr::Sym(sym)
+    }
+}
+
+fn k_intern(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (name, Str));
+    Ok(symbol_expr(name, intern(name)))
+}
+
+fn k_intern_soft(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (name, Any));
+    match name {
+        Expr::Str(name) => Ok(match intern_soft(name.as_str()) {
+            Some(sym) => symbol_expr(name, sym),
+            None => knil(),
+        }),
+        Expr::Sym(sym) if sym.is_interned() => Ok(name.clone()),
+        Expr::Sym(_) => Ok(knil()),
+        Expr::Nil => Ok(knil()),
+        name => Err(E::Type(Type::Str, name.clone())),
+    }
+}
+
+fn k_make_symbol(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (name, Str));
+    Ok(Expr::Sym(make_symbol(name)))
+}
+
+fn k_gensym(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, &optional(prefix, Any));
+    let prefix = match prefix {
+        Some(Expr::Str(prefix)) => prefix.as_str(),
+        Some(Expr::Nil) | None => "g",
+        Some(prefix) => return Err(E::Type(Type::Str, prefix.clone())),
+    };
+    Ok(Expr::Sym(gensym(prefix)))
+}
+
+fn k_symbol_name(_: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    match sym {
+        Expr::Sym(sym) => Ok(kstr(&*sym.name())),
+        Expr::Nil => Ok(kstr("nil")),
+        sym => Err(E::Type(Type::Sym, sym.clone())),
+    }
+}
+
+// the symbol whose cells `expr` refers to. nil has cells like any other symbol.
+fn symbol_of(expr: &Expr) -> Result<Symbol> {
+    match expr {
+        Expr::Sym(sym) => Ok(sym.clone()),
+        Expr::Nil => Ok(intern("nil")),
+        expr => Err(E::Type(Type::Sym, expr.clone())),
+    }
+}
+
+fn k_symbol_value(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    match sym {
+        Expr::Nil | Expr::Keyword(_) => Ok(sym.clone()),
+        sym => env.find_dynamic(symbol_of(sym)?),
+    }
+}
+
+fn k_boundp(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    let bound = match sym {
+        Expr::Nil | Expr::Keyword(_) => true,
+        sym => env.find_dynamic(symbol_of(sym)?).is_ok(),
+    };
+    Ok(kbool(bound))
+}
+
+fn k_makunbound(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    env.makunbound(symbol_of(sym)?)?;
+    Ok(sym.clone())
+}
+
+// the function cell is nil when it is void
+fn k_symbol_function(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    match env.ffind(symbol_of(sym)?) {
+        Ok(Proc::Expr(f)) => Ok(f.deref().clone()),
+        Ok(f) => Ok(kproc(f)),
+        Err(E::Unbound(_)) => Ok(knil()),
+        Err(e) => Err(e),
+    }
+}
+
+fn k_fboundp(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    Ok(kbool(env.ffind(symbol_of(sym)?).is_ok()))
+}
+
+fn k_fmakunbound(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    env.fmakunbound(symbol_of(sym)?)?;
+    Ok(sym.clone())
+}
+
+fn k_get(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any)(prop, Any));
+    Ok(env.get(symbol_of(sym)?, prop))
+}
+
+fn k_put(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any)(prop, Any)(value, Any));
+    env.put(symbol_of(sym)?, prop.clone(), value.clone())?;
+    Ok(value.clone())
+}
+
+fn k_symbol_plist(env: &mut Env, args: &Expr) -> Result<Expr> {
+    get_args!(args, (sym, Any));
+    Ok(env.plist(symbol_of(sym)?))
+}
+
+pub fn init(env: &mut Env) -> Result<()> {
+    env.fregister("intern", kprim("k_intern", k_intern));
+    env.fregister("intern-soft", kprim("k_intern_soft", k_intern_soft));
+    env.fregister("make-symbol", kprim("k_make_symbol", k_make_symbol));
+    env.fregister("gensym", kprim("k_gensym", k_gensym));
+    env.fregister("symbol-name", kprim("k_symbol_name", k_symbol_name));
+    env.fregister("symbol-value", kprim("k_symbol_value", k_symbol_value));
+    env.fregister("boundp", kprim("k_boundp", k_boundp));
+    env.fregister("makunbound", kprim("k_makunbound", k_makunbound));
+    env.fregister(
+        "symbol-function",
+        kprim("k_symbol_function", k_symbol_function),
+    );
+    env.fregister("fboundp", kprim("k_fboundp", k_fboundp));
+    env.fregister("fmakunbound", kprim("k_fmakunbound", k_fmakunbound));
+    env.fregister("get", kprim("k_get", k_get));
+    env.fregister("put", kprim("k_put", k_put));
+    env.fregister("symbol-plist", kprim("k_symbol_plist", k_symbol_plist));
+    // functions are named by symbols, so their properties are the symbols' ones
+    env.fregister("function-get", kprim("k_get", k_get));
+    env.fregister("function-put", kprim("k_put", k_put));
+    Ok(())
+}
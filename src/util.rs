@@ -1,8 +1,29 @@
+use std::cell::{Cell, RefCell};
+use std::mem::size_of;
 use std::ops::Deref;
 use std::rc::Rc;
 
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
 use env::Env;
 use expr::{Error as E, Expr, Kfloat, Kint, Proc, Result, Type};
+use hash::HashTable;
+use symbol::intern;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// approximate bytes of Lisp objects made by this thread so far
+pub fn allocated() -> usize {
+    ALLOCATED.with(|a| a.get())
+}
+
+#[inline]
+pub(crate) fn count_allocation(bytes: usize) {
+    ALLOCATED.with(|a| a.set(a.get().wrapping_add(bytes)));
+}
 
 #[inline]
 pub fn kbool(b: bool) -> Expr {
@@ -22,8 +43,20 @@ pub fn kfloat(f: Kfloat) -> Expr {
     Expr::Float(f)
 }
 
+/// an integer of any size. It is an `Expr::Int` if it fits in `Kint`.
+pub fn kbigint(i: BigInt) -> Expr {
+    match i.to_isize() {
+        Some(i) => Expr::Int(i),
+        None => {
+            count_allocation(i.bits() as usize / 8);
+            Expr::Bignum(Rc::new(i))
+        }
+    }
+}
+
 #[inline]
 pub fn kcons(car: Expr, cdr: Expr) -> Expr {
+    count_allocation(2 * size_of::<Expr>());
     Expr::Cons(Rc::new(car), Rc::new(cdr))
 }
 
@@ -33,8 +66,8 @@ pub fn knil() -> Expr {
 }
 
 #[inline]
-pub fn ksym<S: Into<String>>(s: S) -> Expr {
-    Expr::Sym(Rc::new(s.into()))
+pub fn ksym<S: AsRef<str>>(s: S) -> Expr {
+    Expr::Sym(intern(s.as_ref()))
 }
 
 #[inline]
@@ -44,7 +77,21 @@ pub fn kkw<S: Into<String>>(s: S) -> Expr {
 
 #[inline]
 pub fn kstr<S: Into<String>>(s: S) -> Expr {
-    Expr::Str(Rc::new(s.into()))
+    let s = s.into();
+    count_allocation(s.len());
+    Expr::Str(Rc::new(s))
+}
+
+#[inline]
+pub fn kvector(v: Vec<Expr>) -> Expr {
+    count_allocation(v.len() * size_of::<Expr>());
+    Expr::Vector(Rc::new(RefCell::new(v)))
+}
+
+#[inline]
+pub fn khash_table(h: HashTable) -> Expr {
+    count_allocation(size_of::<HashTable>());
+    Expr::HashTable(Rc::new(RefCell::new(h)))
 }
 
 #[inline]
@@ -59,7 +106,7 @@ pub fn kmacro(p: Proc) -> Expr {
 
 #[inline]
 pub fn klambda(param: Expr, body: Expr) -> Proc {
-    Proc::Lambda(Rc::new(param), Rc::new(body))
+    Proc::Lambda(Rc::new(param), Rc::new(body), None)
 }
 
 #[inline]
@@ -148,7 +195,7 @@ macro_rules! get_args_one {
     );
     ($v:expr, Bool) => (
         match $v {
-            &Expr::Nil => Ok(false),
+            &Expr::Nil => Ok::<_, E>(false),
             _ => Ok(true)
         }
     );
@@ -158,6 +205,18 @@ macro_rules! get_args_one {
             hd => Err(E::Type(Type::Cons, hd.clone()))
         }
     );
+    ($v:expr, Vector) => (
+        match $v {
+            &Expr::Vector(ref v) => Ok(v),
+            hd => Err(E::Type(Type::Vector, hd.clone()))
+        }
+    );
+    ($v:expr, HashTable) => (
+        match $v {
+            &Expr::HashTable(ref h) => Ok(h),
+            hd => Err(E::Type(Type::HashTable, hd.clone()))
+        }
+    );
     ($v:expr, Proc) => (
         match $v {
             &Expr::Proc(ref p) => Ok(p),
@@ -167,11 +226,11 @@ macro_rules! get_args_one {
     ($v:expr, Any) => (
         match $v {
             hd => if true {
-                Ok(hd)
+                Ok::<_, E>(hd)
             } else {
                 unreachable!()
             }
-        };
+        }
     )
 }
 
@@ -195,7 +254,7 @@ macro_rules! gen_match {
                 },
                 Expr::Nil => return Err(E::ArityShort),
                 args => return Err(E::InvalidArgument(args.clone()))
-            };
+            }
         );
     ($args: expr, &optional ($var: pat, $($ident: tt)+) $($other:tt) *) =>
         (
@@ -208,7 +267,7 @@ macro_rules! gen_match {
                     (None, gen_match!($args, &optional $($other)*))
                 },
                 args => return Err(E::InvalidArgument(args.clone()))
-            };
+            }
         );
     ($args: expr, &optional) => (
         match $args {
@@ -265,26 +324,38 @@ pub fn f_foldr<F>(env: &mut Env, f: &F, init: &Expr, args: &Expr) -> Result<Expr
 where
     F: Fn(&mut Env, &Expr, &Expr) -> Result<Expr>,
 {
-    match args {
-        Expr::Nil => Ok(init.clone()),
-        Expr::Cons(car, cdr) => {
-            let v = f_foldr(env, f, init, cdr)?;
-            f(env, &v, car)
-        }
-        args => Err(E::InvalidArgument(args.clone())),
+    // walks the list first so that long lists don't use up the stack
+    let mut elements = Vec::new();
+    let mut head = args;
+    while let Expr::Cons(car, cdr) = head {
+        elements.push(car.deref());
+        head = cdr;
+    }
+    if head != &Expr::Nil {
+        return Err(E::InvalidArgument(head.clone()));
+    }
+    let mut res = init.clone();
+    for x in elements.into_iter().rev() {
+        res = f(env, &res, x)?;
     }
+    Ok(res)
 }
 
+/// applies `f` to the elements of `list` from the first one
 pub fn f_map<F>(env: &mut Env, f: &F, list: &Expr) -> Result<Expr>
 where
     F: Fn(&mut Env, &Expr) -> Result<Expr>,
 {
-    f_foldr(
-        env,
-        &|env, acc, x| Ok(kcons(f(env, x)?, acc.clone())),
-        &knil(),
-        list,
-    )
+    let mut values = Vec::new();
+    let mut head = list;
+    while let Expr::Cons(car, cdr) = head {
+        values.push(f(env, car)?);
+        head = cdr;
+    }
+    if head != &Expr::Nil {
+        return Err(E::InvalidArgument(head.clone()));
+    }
+    Ok(values.into_iter().rev().fold(knil(), |acc, x| kcons(x, acc)))
 }
 
 // fn f_iter<F>(mut env: &mut Env, f: &F, list: &Expr) -> Result<Expr>
@@ -1,5 +1,6 @@
 #[macro_use]
 extern crate kappa_lisp;
+use kappa_lisp::expr::{Error as E, Kfloat};
 use kappa_lisp::run_new;
 use kappa_lisp::util::*;
 
@@ -45,6 +46,78 @@ fn test_div() {
     assert_eq!(run_new("(/ 3 2)"), Ok(kint(1)));
     assert_eq!(run_new("(/ 3 2.0)"), Ok(kfloat(1.5)));
     assert_eq!(run_new("(/ 3 2 1)"), Ok(kint(1)));
+    assert_eq!(run_new("(/ -7 2)"), Ok(kint(-3)));
+    assert_eq!(run_new("(/ 5 2 2.0)"), Ok(kfloat(1.25)));
+    assert_eq!(run_new("(/ 2)"), Ok(kint(0)));
+    assert_eq!(run_new("(/ 1.0 0)"), Ok(kfloat(Kfloat::INFINITY)));
+    assert_eq!(run_new("(/ -1 0.0)"), Ok(kfloat(Kfloat::NEG_INFINITY)));
+}
+
+#[test]
+fn test_div_by_zero() {
+    let error = Err(E::Signal(ksym("arith-error"), knil()));
+    assert_eq!(run_new("(/ 1 0)"), error);
+    assert_eq!(run_new("(/ 0)"), error);
+    assert_eq!(run_new("(% 1 0)"), error);
+    assert_eq!(run_new("(mod 1 0)"), error);
+    assert_eq!(run_new("(floor 1 0)"), error);
+    assert_eq!(run_new("(round 1.0 0.0)"), error);
+    assert_eq!(
+        run_new("(condition-case nil (/ 1 0) (arith-error 'caught))"),
+        Ok(ksym("caught"))
+    );
+}
+
+#[test]
+fn test_rem_mod() {
+    assert_eq!(run_new("(% 7 2)"), Ok(kint(1)));
+    assert_eq!(run_new("(% -7 2)"), Ok(kint(-1)));
+    assert_eq!(run_new("(% 7 -2)"), Ok(kint(1)));
+    assert!(run_new("(% 7.0 2)").is_err());
+    assert_eq!(run_new("(mod 7 2)"), Ok(kint(1)));
+    assert_eq!(run_new("(mod -7 2)"), Ok(kint(1)));
+    assert_eq!(run_new("(mod 7 -2)"), Ok(kint(-1)));
+    assert_eq!(run_new("(mod 5.5 2)"), Ok(kfloat(1.5)));
+    assert_eq!(run_new("(mod -5.5 2)"), Ok(kfloat(0.5)));
+}
+
+#[test]
+fn test_rounding() {
+    assert_eq!(run_new("(floor 2.5)"), Ok(kint(2)));
+    assert_eq!(run_new("(floor -2.5)"), Ok(kint(-3)));
+    assert_eq!(run_new("(ceiling 2.5)"), Ok(kint(3)));
+    assert_eq!(run_new("(truncate -2.5)"), Ok(kint(-2)));
+    assert_eq!(run_new("(round 2.5)"), Ok(kint(2)));
+    assert_eq!(run_new("(round 3.5)"), Ok(kint(4)));
+    assert_eq!(run_new("(round -2.6)"), Ok(kint(-3)));
+    assert_eq!(run_new("(floor 7)"), Ok(kint(7)));
+    assert_eq!(run_new("(floor 7 2)"), Ok(kint(3)));
+    assert_eq!(run_new("(floor -7 2)"), Ok(kint(-4)));
+    assert_eq!(run_new("(ceiling 7 2)"), Ok(kint(4)));
+    assert_eq!(run_new("(ceiling -7 2)"), Ok(kint(-3)));
+    assert_eq!(run_new("(truncate -7 2)"), Ok(kint(-3)));
+    assert_eq!(run_new("(round 5 2)"), Ok(kint(2)));
+    assert_eq!(run_new("(round 7 2)"), Ok(kint(4)));
+    assert_eq!(run_new("(round -7 2)"), Ok(kint(-4)));
+    assert_eq!(run_new("(round 8 3)"), Ok(kint(3)));
+    assert_eq!(run_new("(floor 7 2.0)"), Ok(kint(3)));
+    assert_eq!(run_new("(floor 7 nil)"), Ok(kint(7)));
+    assert_eq!(
+        run_new("(floor (/ 1.0 0))"),
+        Err(E::Signal(ksym("overflow-error"), knil()))
+    );
+    assert!(run_new("(floor 'a)").is_err());
+}
+
+#[test]
+fn test_float() {
+    assert_eq!(run_new("(float 1)"), Ok(kfloat(1.0)));
+    assert_eq!(run_new("(float 1.5)"), Ok(kfloat(1.5)));
+    assert_eq!(run_new("(ffloor -1.5)"), Ok(kfloat(-2.0)));
+    assert_eq!(run_new("(fceiling 1.5)"), Ok(kfloat(2.0)));
+    assert_eq!(run_new("(fround 2.5)"), Ok(kfloat(2.0)));
+    assert_eq!(run_new("(ftruncate -1.5)"), Ok(kfloat(-1.0)));
+    assert!(run_new("(ffloor 1)").is_err());
 }
 
 #[test]
@@ -52,8 +125,8 @@ fn test_gt() {
     assert_eq!(run_new("(> 1 2)"), Ok(kbool(false)));
     assert_eq!(run_new("(> 1 1)"), Ok(kbool(false)));
     assert_eq!(run_new("(> 2 1)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(> -1 1.0)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(> 1.0 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(> -1 1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(> 1.0 -1)"), Ok(kbool(true)));
 }
 
 #[test]
@@ -61,8 +134,8 @@ fn test_ge() {
     assert_eq!(run_new("(>= 1 2)"), Ok(kbool(false)));
     assert_eq!(run_new("(>= 1 1)"), Ok(kbool(true)));
     assert_eq!(run_new("(>= 2 1)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(>= -1 1.0)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(>= 1.0 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(>= -1 1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(>= 1.0 -1)"), Ok(kbool(true)));
 }
 
 #[test]
@@ -70,8 +143,8 @@ fn test_lt() {
     assert_eq!(run_new("(< 1 2)"), Ok(kbool(true)));
     assert_eq!(run_new("(< 1 1)"), Ok(kbool(false)));
     assert_eq!(run_new("(< 2 1)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(< -1 1.0)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(< 1.0 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(< -1 1.0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(< 1.0 -1)"), Ok(kbool(false)));
 }
 
 #[test]
@@ -79,8 +152,8 @@ fn test_le() {
     assert_eq!(run_new("(<= 1 2)"), Ok(kbool(true)));
     assert_eq!(run_new("(<= 1 1)"), Ok(kbool(true)));
     assert_eq!(run_new("(<= 2 1)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(<= -1 1.0)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(<= 1.0 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(<= -1 1.0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(<= 1.0 -1)"), Ok(kbool(false)));
 }
 
 #[test]
@@ -88,9 +161,9 @@ fn test_eq() {
     assert_eq!(run_new("(= 1 2)"), Ok(kbool(false)));
     assert_eq!(run_new("(= 1 1)"), Ok(kbool(true)));
     assert_eq!(run_new("(= 2 1)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(= -1 1.0)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(= -1 -1.0)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(= 1.0 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(= -1 1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(= -1 -1.0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(= 1.0 -1)"), Ok(kbool(false)));
 }
 
 #[test]
@@ -98,9 +171,60 @@ fn test_neq() {
     assert_eq!(run_new("(/= 1 2)"), Ok(kbool(true)));
     assert_eq!(run_new("(/= 1 1)"), Ok(kbool(false)));
     assert_eq!(run_new("(/= 2 1)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(/= -1 1.0)"), Ok(kbool(true)));
-    // assert_eq!(run_new("(/= -1 -1.0)"), Ok(kbool(false)));
-    // assert_eq!(run_new("(/= 1.0 -1)"), Ok(kbool(true)));
+    assert_eq!(run_new("(/= -1 1.0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(/= -1 -1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(/= 1.0 -1)"), Ok(kbool(true)));
+}
+
+#[test]
+fn test_variadic_cmp() {
+    assert_eq!(run_new("(< 1 2 3)"), Ok(kbool(true)));
+    assert_eq!(run_new("(< 1 3 2)"), Ok(kbool(false)));
+    assert_eq!(run_new("(<= 1 1 2.5)"), Ok(kbool(true)));
+    assert_eq!(run_new("(> 3 2.5 2)"), Ok(kbool(true)));
+    assert_eq!(run_new("(= 1 1.0 1)"), Ok(kbool(true)));
+    assert_eq!(run_new("(/= 1 2 1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(/= 1 2 3)"), Ok(kbool(true)));
+    assert_eq!(run_new("(< 1)"), Ok(kbool(true)));
+    assert!(run_new("(<)").is_err());
+    assert!(run_new("(< 1 'a)").is_err());
+    assert!(run_new("(< 1 2 \"3\")").is_err());
+}
+
+#[test]
+fn test_max_min() {
+    assert_eq!(run_new("(max 1 3 2)"), Ok(kint(3)));
+    assert_eq!(run_new("(min 1 3 2)"), Ok(kint(1)));
+    assert_eq!(run_new("(max 3 2.0)"), Ok(kfloat(3.0)));
+    assert_eq!(run_new("(min 1.5 2)"), Ok(kfloat(1.5)));
+    assert_eq!(run_new("(max -1)"), Ok(kint(-1)));
+    assert!(run_new("(max)").is_err());
+    assert!(run_new("(min 1 nil)").is_err());
+}
+
+#[test]
+fn test_number_predicates() {
+    assert_eq!(run_new("(zerop 0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(zerop 0.0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(zerop 1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(plusp 1)"), Ok(kbool(true)));
+    assert_eq!(run_new("(plusp 0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(minusp -0.5)"), Ok(kbool(true)));
+    assert_eq!(run_new("(natnump 0)"), Ok(kbool(true)));
+    assert_eq!(run_new("(natnump -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(natnump 1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(natnump 'a)"), Ok(kbool(false)));
+    assert!(run_new("(zerop 'a)").is_err());
+}
+
+#[test]
+fn test_inc_dec_abs() {
+    assert_eq!(run_new("(1+ 1)"), Ok(kint(2)));
+    assert_eq!(run_new("(1- 1)"), Ok(kint(0)));
+    assert_eq!(run_new("(1+ 1.5)"), Ok(kfloat(2.5)));
+    assert_eq!(run_new("(abs -3)"), Ok(kint(3)));
+    assert_eq!(run_new("(abs -2.5)"), Ok(kfloat(2.5)));
+    assert!(run_new("(1+ \"1\")").is_err());
 }
 
 #[test]
@@ -152,6 +276,31 @@ fn test_substring() {
     assert_eq!(run_new("(substring \"abcdefg\" 4 6)"), Ok(kstr("ef")));
 }
 
+#[test]
+fn test_char_to_string() {
+    assert_eq!(run_new("(char-to-string ?a)"), Ok(kstr("a")));
+    assert_eq!(run_new("(char-to-string 12354)"), Ok(kstr("あ")));
+    assert!(run_new("(char-to-string -1)").is_err());
+}
+
+#[test]
+fn test_string_to_char() {
+    assert_eq!(run_new("(string-to-char \"abc\")"), Ok(kint(97)));
+    assert_eq!(run_new("(string-to-char \"あ\")"), Ok(kint(12354)));
+    assert_eq!(run_new("(string-to-char \"\")"), Ok(kint(0)));
+}
+
+#[test]
+fn test_char_equal() {
+    assert_eq!(run_new("(char-equal ?a ?a)"), Ok(kbool(true)));
+    assert_eq!(run_new("(char-equal ?a ?A)"), Ok(kbool(true)));
+    assert_eq!(run_new("(char-equal ?a ?b)"), Ok(kbool(false)));
+    assert_eq!(
+        run_new("(progn (set 'case-fold-search nil) (char-equal ?a ?A))"),
+        Ok(kbool(false))
+    );
+}
+
 #[test]
 fn test_t() {
     assert_eq!(run_new("t"), Ok(ksym("t")))
@@ -0,0 +1,130 @@
+#[macro_use]
+extern crate kappa_lisp;
+use kappa_lisp::read;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, run_new, Env};
+
+#[test]
+fn test_promotion() {
+    assert_eq!(
+        run_new("(* 99999999999 99999999999)").unwrap().to_string(),
+        "9999999999800000000001"
+    );
+    assert_eq!(
+        run_new("(+ 9223372036854775807 1)").unwrap().to_string(),
+        "9223372036854775808"
+    );
+    assert_eq!(
+        run_new("(- -9223372036854775808 1)").unwrap().to_string(),
+        "-9223372036854775809"
+    );
+    assert_eq!(
+        run_new("(1+ 9223372036854775807)").unwrap().to_string(),
+        "9223372036854775808"
+    );
+    assert_eq!(
+        run_new("(abs -9223372036854775808)").unwrap().to_string(),
+        "9223372036854775808"
+    );
+    assert_eq!(
+        run_new("(/ -9223372036854775808 -1)").unwrap().to_string(),
+        "9223372036854775808"
+    );
+}
+
+#[test]
+fn test_demotion() {
+    assert_eq!(
+        run_new("(- (* 99999999999 99999999999) 9999999999800000000000)"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run_new("(/ (* 99999999999 99999999999) 99999999999)"),
+        Ok(kint(99999999999))
+    );
+    assert_eq!(run_new("(% 100000000000000000000 7)"), Ok(kint(2)));
+    assert_eq!(run_new("(mod -100000000000000000000 7)"), Ok(kint(5)));
+    assert_eq!(
+        run_new("(floor 100000000000000000000 30000000000000000000)"),
+        Ok(kint(3))
+    );
+}
+
+#[test]
+fn test_read_print() {
+    let big = read("123456789012345678901234567890").unwrap();
+    assert_eq!(big.to_string(), "123456789012345678901234567890");
+    assert_eq!(
+        read("-9223372036854775809").unwrap().to_string(),
+        "-9223372036854775809"
+    );
+    assert_eq!(read("9223372036854775807"), Ok(kint(9223372036854775807)));
+    assert_eq!(
+        read("#x10000000000000000").unwrap().to_string(),
+        "18446744073709551616"
+    );
+    assert_eq!(read("#x-10"), Ok(kint(-16)));
+    assert_eq!(
+        run_new("(string-to-number \"123456789012345678901234567890\")")
+            .unwrap()
+            .to_string(),
+        "123456789012345678901234567890"
+    );
+}
+
+#[test]
+fn test_compare() {
+    assert_eq!(
+        run_new("(= (* 99999999999 99999999999) 9999999999800000000001)"),
+        Ok(kbool(true))
+    );
+    assert_eq!(
+        run_new("(< 1 100000000000000000000 100000000000000000001)"),
+        Ok(kbool(true))
+    );
+    assert_eq!(run_new("(> -100000000000000000000 -1)"), Ok(kbool(false)));
+    assert_eq!(run_new("(< 1e10 100000000000000000000)"), Ok(kbool(true)));
+    assert_eq!(
+        run_new("(max 1 100000000000000000000)")
+            .unwrap()
+            .to_string(),
+        "100000000000000000000"
+    );
+    assert_eq!(run_new("(natnump 100000000000000000000)"), Ok(kbool(true)));
+    assert_eq!(
+        run_new("(eql 100000000000000000000 100000000000000000000)"),
+        Ok(kbool(true))
+    );
+    assert_eq!(
+        run_new("(equal (list 100000000000000000000) (list 100000000000000000000))"),
+        Ok(kbool(true))
+    );
+}
+
+#[test]
+fn test_float_contagion() {
+    assert_eq!(run_new("(+ 100000000000000000000 0.0)"), Ok(kfloat(1e20)));
+    assert_eq!(run_new("(float 100000000000000000000)"), Ok(kfloat(1e20)));
+    // 2^70 is exact in a float
+    assert_eq!(
+        run_new("(truncate 1180591620717411303424.0)")
+            .unwrap()
+            .to_string(),
+        "1180591620717411303424"
+    );
+}
+
+#[test]
+fn test_skk_calc_bignum() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(set 'skk-num-list '(99999999999 99999999999))").unwrap();
+    assert_eq!(
+        run(&mut env, "(skk-calc '*)").unwrap().to_string(),
+        "9999999999800000000001"
+    );
+    assert_eq!(
+        run(&mut env, "(list (skk-calc '+))"),
+        Ok(klist!(kint(199999999998)))
+    );
+}
@@ -1,5 +1,10 @@
 #[macro_use]
 extern crate kappa_lisp;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use kappa_lisp::env::Frame;
+use kappa_lisp::expr::Expr;
 use kappa_lisp::util::*;
 use kappa_lisp::{init, run, run_new, Env};
 
@@ -87,3 +92,528 @@ fn test_if() {
     assert_eq!(run_new("(if 1 1)"), Ok(kint(1)));
     assert_eq!(run_new("(if nil 1)"), Ok(knil()));
 }
+
+#[test]
+fn test_backquote() {
+    assert_eq!(run_new("`a"), Ok(ksym("a")));
+    assert_eq!(run_new("`(a b)"), Ok(klist!(ksym("a"), ksym("b"))));
+    assert_eq!(run_new("`(a ,(+ 1 2))"), Ok(klist!(ksym("a"), kint(3))));
+    assert_eq!(
+        run_new("`(a ,@(list 1 2) b)"),
+        Ok(klist!(ksym("a"), kint(1), kint(2), ksym("b")))
+    );
+    assert_eq!(run_new("`(,@nil)"), Ok(knil()));
+    assert_eq!(run_new("`(a . ,(+ 1 2))"), Ok(kcons(ksym("a"), kint(3))));
+    assert_eq!(
+        run_new("`(a ,@(cons 1 2))"),
+        Ok(kcons(ksym("a"), kcons(kint(1), kint(2))))
+    );
+    assert_eq!(
+        run_new("`((,(+ 1 2)) \"s\")"),
+        Ok(klist!(klist!(kint(3)), kstr("s")))
+    );
+    assert!(run_new("`(,@(cons 1 2) a)").is_err());
+}
+
+#[test]
+fn test_nested_backquote() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq x 1)").unwrap();
+    assert_eq!(
+        run(&mut env, "`(a `(b ,(c ,x)))"),
+        Ok(klist!(
+            ksym("a"),
+            klist!(
+                ksym("`"),
+                klist!(ksym("b"), klist!(ksym(","), klist!(ksym("c"), kint(1))))
+            )
+        ))
+    );
+    assert_eq!(
+        run(&mut env, "`(a `(b ,,x))"),
+        Ok(klist!(
+            ksym("a"),
+            klist!(ksym("`"), klist!(ksym("b"), klist!(ksym(","), kint(1))))
+        ))
+    );
+}
+
+#[test]
+fn test_defmacro() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(
+        &mut env,
+        "(defmacro my-inc (place) `(setq ,place (+ ,place 1)))",
+    )
+    .unwrap();
+    run(&mut env, "(setq n 1)").unwrap();
+    run(&mut env, "(my-inc n)").unwrap();
+    assert_eq!(run(&mut env, "n"), Ok(kint(2)));
+    run(&mut env, "(defun twice (x) (* x 2))").unwrap();
+    assert_eq!(run(&mut env, "(twice 4)"), Ok(kint(8)));
+}
+
+#[test]
+fn test_let() {
+    assert_eq!(run_new("(let ((x 1) (y 2)) (+ x y))"), Ok(kint(3)));
+    assert_eq!(
+        run_new("(let (x (y) (z 1)) (list x y z))"),
+        Ok(klist!(knil(), knil(), kint(1)))
+    );
+    assert_eq!(run_new("(let ((x 1)) (let ((x 2) (y x)) y))"), Ok(kint(1)));
+    assert_eq!(run_new("(let ((x 1)) (let ((x 2)) x) x)"), Ok(kint(1)));
+    assert_eq!(run_new("(let () 1 2)"), Ok(kint(2)));
+    assert_eq!(run_new("(let ((x 1)) (setq x 2) x)"), Ok(kint(2)));
+    assert!(run_new("(let ((x 1 2)) x)").is_err());
+}
+
+#[test]
+fn test_let_star() {
+    assert_eq!(
+        run_new("(let* ((x 1) (y (+ x 1))) (list x y))"),
+        Ok(klist!(kint(1), kint(2)))
+    );
+    assert_eq!(run_new("(let ((x 1)) (let* ((x 2) (y x)) y))"), Ok(kint(2)));
+}
+
+#[test]
+fn test_let_scope() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq x 'global)").unwrap();
+    assert_eq!(run(&mut env, "(let ((x 'local)) x)"), Ok(ksym("local")));
+    assert_eq!(run(&mut env, "x"), Ok(ksym("global")));
+    // setq on a let-bound variable doesn't leak out
+    assert_eq!(run(&mut env, "(let ((x 1)) (setq x 2))"), Ok(knil()));
+    assert_eq!(run(&mut env, "x"), Ok(ksym("global")));
+    // the frame is removed when the body fails
+    assert!(run(&mut env, "(let ((x 'local)) (car 1))").is_err());
+    assert_eq!(run(&mut env, "x"), Ok(ksym("global")));
+    assert!(run(&mut env, "(let* ((x 'local) (y (car 1))) x)").is_err());
+    assert_eq!(run(&mut env, "x"), Ok(ksym("global")));
+    assert!(run(&mut env, "((lambda (x y) x) 'local)").is_err());
+    assert_eq!(run(&mut env, "x"), Ok(ksym("global")));
+}
+
+#[test]
+fn test_setq_in_function() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq counter 0)").unwrap();
+    run(&mut env, "(defun inc () (setq counter (+ counter 1)))").unwrap();
+    run(&mut env, "(inc)").unwrap();
+    run(&mut env, "(inc)").unwrap();
+    assert_eq!(run(&mut env, "counter"), Ok(kint(2)));
+}
+
+#[test]
+fn test_flet() {
+    assert_eq!(run_new("(flet ((f (x) (* x 2))) (f 3))"), Ok(kint(6)));
+    assert_eq!(
+        run_new("(cl-flet ((f (x) x) (g () 1)) (list (f 0) (g)))"),
+        Ok(klist!(kint(0), kint(1)))
+    );
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(defun f () 'global)").unwrap();
+    assert_eq!(
+        run(&mut env, "(flet ((f () 'local)) (f))"),
+        Ok(ksym("local"))
+    );
+    assert_eq!(run(&mut env, "(f)"), Ok(ksym("global")));
+    assert!(run(&mut env, "(flet ((f () 'local)) (car 1))").is_err());
+    assert_eq!(run(&mut env, "(f)"), Ok(ksym("global")));
+}
+
+#[test]
+fn test_labels() {
+    assert_eq!(
+        run_new("(labels ((fact (n) (if (< n 1) 1 (* n (fact (- n 1)))))) (fact 5))"),
+        Ok(kint(120))
+    );
+    assert_eq!(
+        run_new(
+            "(cl-labels ((even (n) (if (= n 0) t (odd (- n 1))))
+                         (odd (n) (if (= n 0) nil (even (- n 1)))))
+               (list (even 4) (odd 4)))"
+        ),
+        Ok(klist!(ksym("t"), knil()))
+    );
+}
+
+#[test]
+fn test_labels_frames_are_freed() {
+    // `(capture)` keeps a weak reference to the innermost lexical frame
+    let frames: Rc<RefCell<Vec<Weak<Frame>>>> = Rc::new(RefCell::new(Vec::new()));
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    let captured = frames.clone();
+    env.fregister(
+        "capture",
+        kprim("capture", move |env: &mut Env, _: &Expr| {
+            captured
+                .borrow_mut()
+                .push(Rc::downgrade(&env.capture().unwrap()));
+            Ok(knil())
+        }),
+    );
+    run(&mut env, "(labels ((f () 1)) (capture) (f))").unwrap();
+    run(
+        &mut env,
+        "(labels ((f (n) (if (= n 0) (capture) (f (- n 1))))) (capture) (f 3))",
+    )
+    .unwrap();
+    run(
+        &mut env,
+        "(let ((g nil)) (setq g (lambda () g)) (capture) nil)",
+    )
+    .unwrap();
+    // a closure that escapes keeps its frame
+    run(
+        &mut env,
+        "(setq h (labels ((f () (capture) 1)) (capture) #'f))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(funcall h)"), Ok(kint(1)));
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 6);
+    assert!(frames[..4].iter().all(|frame| frame.upgrade().is_none()));
+    assert!(frames[4].upgrade().is_some());
+    assert!(frames[5].upgrade().is_none());
+}
+
+#[test]
+fn test_cond() {
+    assert_eq!(run_new("(cond ((= 1 2) 1) ((= 1 1) 2 3))"), Ok(kint(3)));
+    assert_eq!(run_new("(cond ((= 1 2) 1))"), Ok(knil()));
+    assert_eq!(run_new("(cond (nil) (5))"), Ok(kint(5)));
+    assert_eq!(run_new("(cond)"), Ok(knil()));
+    assert!(run_new("(cond 1)").is_err());
+}
+
+#[test]
+fn test_and_or() {
+    assert_eq!(run_new("(and)"), Ok(ksym("t")));
+    assert_eq!(run_new("(and 1 2)"), Ok(kint(2)));
+    assert_eq!(run_new("(and nil undefined)"), Ok(knil()));
+    assert_eq!(run_new("(or)"), Ok(knil()));
+    assert_eq!(run_new("(or nil 2 undefined)"), Ok(kint(2)));
+    assert_eq!(run_new("(or nil nil)"), Ok(knil()));
+}
+
+#[test]
+fn test_not() {
+    assert_eq!(run_new("(not nil)"), Ok(ksym("t")));
+    assert_eq!(run_new("(not 1)"), Ok(knil()));
+    assert_eq!(run_new("(null '(1))"), Ok(knil()));
+    assert_eq!(run_new("(null ())"), Ok(ksym("t")));
+}
+
+#[test]
+fn test_when_unless() {
+    assert_eq!(run_new("(when (= 1 1) 1 2)"), Ok(kint(2)));
+    assert_eq!(run_new("(when nil 1)"), Ok(knil()));
+    assert_eq!(run_new("(unless nil 1 2)"), Ok(kint(2)));
+    assert_eq!(run_new("(unless t 1)"), Ok(knil()));
+}
+
+#[test]
+fn test_while() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq i 0)").unwrap();
+    run(&mut env, "(setq sum 0)").unwrap();
+    assert_eq!(
+        run(&mut env, "(while (< i 5) (setq sum (+ sum i)) (setq i (+ i 1)))"),
+        Ok(knil())
+    );
+    assert_eq!(run(&mut env, "sum"), Ok(kint(10)));
+}
+
+#[test]
+fn test_dolist() {
+    assert_eq!(
+        run_new("(let ((sum 0)) (dolist (x '(1 2 3) sum) (setq sum (+ sum x))))"),
+        Ok(kint(6))
+    );
+    assert_eq!(run_new("(dolist (x '(1 2)))"), Ok(knil()));
+}
+
+#[test]
+fn test_dotimes() {
+    assert_eq!(
+        run_new("(let ((l ())) (dotimes (i 3 l) (setq l (cons i l))))"),
+        Ok(klist!(kint(2), kint(1), kint(0)))
+    );
+    assert_eq!(run_new("(dotimes (i 0) undefined)"), Ok(knil()));
+}
+
+#[test]
+fn test_assoc_long_list() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq l nil)").unwrap();
+    run(&mut env, "(dotimes (i 10000) (setq l (cons (cons i i) l)))").unwrap();
+    assert_eq!(run(&mut env, "(assoc 0 l)"), Ok(kcons(kint(0), kint(0))));
+    assert_eq!(run(&mut env, "(assoc -1 l)"), Ok(knil()));
+}
+
+#[test]
+fn test_catch_throw() {
+    assert_eq!(run_new("(catch 'done (throw 'done 1) 2)"), Ok(kint(1)));
+    assert_eq!(run_new("(catch 'done 1 2)"), Ok(kint(2)));
+    assert_eq!(
+        run_new("(catch 'outer (catch 'inner (throw 'outer 1)) 2)"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run_new("(let ((x 1)) (catch 'done (let ((x 2)) (throw 'done x))) x)"),
+        Ok(kint(1))
+    );
+    assert!(run_new("(throw 'done 1)").is_err());
+    // throws pass through condition-case
+    assert_eq!(
+        run_new("(catch 'done (condition-case nil (throw 'done 1) (error 2)))"),
+        Ok(kint(1))
+    );
+}
+
+#[test]
+fn test_condition_case() {
+    assert_eq!(
+        run_new("(condition-case e (signal 'my-error '(1 2)) (my-error e))"),
+        Ok(klist!(ksym("my-error"), kint(1), kint(2)))
+    );
+    assert_eq!(
+        run_new("(condition-case e (error \"oops\") (error (error-message-string e)))"),
+        Ok(kstr("oops"))
+    );
+    assert_eq!(
+        run_new("(condition-case nil (car 1) ((void-variable wrong-type-argument) 1))"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run_new("(condition-case e (car 1) (wrong-type-argument e))"),
+        Ok(klist!(ksym("wrong-type-argument"), ksym("consp"), kint(1)))
+    );
+    assert_eq!(
+        run_new("(condition-case e undefined (void-variable (cdr e)))"),
+        Ok(klist!(ksym("undefined")))
+    );
+    assert_eq!(
+        run_new("(condition-case e 1 (error 2) (:success (+ e 1)))"),
+        Ok(kint(2))
+    );
+    assert_eq!(run_new("(condition-case e 1 (error 2))"), Ok(kint(1)));
+    assert!(run_new("(condition-case e (car 1) (void-variable 1))").is_err());
+    assert_eq!(
+        run_new("(let ((x 1)) (condition-case nil (let ((x 2)) (car x)) (error x)))"),
+        Ok(kint(1))
+    );
+}
+
+#[test]
+fn test_ignore_errors() {
+    assert_eq!(run_new("(ignore-errors (car 1) 2)"), Ok(knil()));
+    assert_eq!(run_new("(ignore-errors 1 2)"), Ok(kint(2)));
+}
+
+#[test]
+fn test_unwind_protect() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq x 0)").unwrap();
+    assert_eq!(
+        run(&mut env, "(unwind-protect 1 (setq x (+ x 1)))"),
+        Ok(kint(1))
+    );
+    assert!(run(&mut env, "(unwind-protect (car 1) (setq x (+ x 1)))").is_err());
+    assert_eq!(
+        run(
+            &mut env,
+            "(catch 'done (unwind-protect (throw 'done 2) (setq x (+ x 1))))"
+        ),
+        Ok(kint(2))
+    );
+    assert_eq!(run(&mut env, "x"), Ok(kint(3)));
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((x 10)) (catch 'done (let ((x 20)) (unwind-protect (throw 'done 1) (setq y x)))) (list x y))"
+        ),
+        Ok(klist!(kint(10), kint(20)))
+    );
+}
+
+#[test]
+fn test_closure() {
+    assert_eq!(
+        run_new("(funcall (let ((x 1)) (lambda () x)))"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run_new("(funcall (let ((x 1)) (lambda (y) (+ x y))) 2)"),
+        Ok(kint(3))
+    );
+    // closures share the captured bindings
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(
+        &mut env,
+        "(defun make-counter () (let ((n 0)) (lambda () (setq n (+ n 1)) n)))",
+    )
+    .unwrap();
+    run(&mut env, "(setq c1 (make-counter))").unwrap();
+    run(&mut env, "(setq c2 (make-counter))").unwrap();
+    run(&mut env, "(funcall c1)").unwrap();
+    assert_eq!(run(&mut env, "(funcall c1)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(funcall c2)"), Ok(kint(1)));
+}
+
+#[test]
+fn test_lexical_scope() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(defun get-x () x)").unwrap();
+    // callers' locals are not visible
+    assert!(run(&mut env, "(let ((x 1)) (get-x))").is_err());
+    run(&mut env, "(setq x 0)").unwrap();
+    assert_eq!(run(&mut env, "(let ((x 1)) (get-x))"), Ok(kint(0)));
+    // functions defined in a local scope are global
+    run(&mut env, "(let ((y 2)) (defun get-y () y))").unwrap();
+    assert_eq!(run(&mut env, "(get-y)"), Ok(kint(2)));
+}
+
+#[test]
+fn test_defvar() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    assert_eq!(run(&mut env, "(defvar depth 0)"), Ok(ksym("depth")));
+    run(&mut env, "(defun get-depth () depth)").unwrap();
+    assert_eq!(run(&mut env, "(let ((depth 1)) (get-depth))"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(get-depth)"), Ok(kint(0)));
+    // defvar does not override the value
+    run(&mut env, "(defvar depth 2)").unwrap();
+    assert_eq!(run(&mut env, "depth"), Ok(kint(0)));
+    // closures do not capture special variables
+    run(&mut env, "(setq f (let ((depth 3)) (lambda () depth)))").unwrap();
+    assert_eq!(run(&mut env, "(funcall f)"), Ok(kint(0)));
+}
+
+#[test]
+fn test_defconst() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    assert_eq!(
+        run(&mut env, "(defconst limit 10 \"doc\")"),
+        Ok(ksym("limit"))
+    );
+    run(&mut env, "(defconst limit 20)").unwrap();
+    assert_eq!(run(&mut env, "limit"), Ok(kint(20)));
+    run(&mut env, "(defun get-limit () limit)").unwrap();
+    assert_eq!(run(&mut env, "(let ((limit 1)) (get-limit))"), Ok(kint(1)));
+    assert!(run(&mut env, "(defconst no-value)").is_err());
+}
+
+#[test]
+fn test_dynamic_binding() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    env.set_lexical_binding(false);
+    run(&mut env, "(defun get-x () x)").unwrap();
+    assert_eq!(run(&mut env, "(let ((x 1)) (get-x))"), Ok(kint(1)));
+}
+
+#[test]
+fn test_tail_call() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(
+        &mut env,
+        "(defun count-up (n acc) (if (= n 0) acc (count-up (- n 1) (+ acc 1))))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(count-up 10000 0)"), Ok(kint(10000)));
+    run(
+        &mut env,
+        "(defun count-down (n) (cond ((= n 0) 'done) (t (let ((m (- n 1))) (count-down m)))))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(count-down 10000)"), Ok(ksym("done")));
+}
+
+#[test]
+fn test_eval_depth() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(
+        &mut env,
+        "(defun depth (n) (if (= n 0) 0 (+ 1 (depth (- n 1)))))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(depth 100)"), Ok(kint(100)));
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case e (depth 100000) (excessive-lisp-nesting (car e)))"
+        ),
+        Ok(ksym("excessive-lisp-nesting"))
+    );
+    // the depth is restored after the error
+    assert_eq!(run(&mut env, "(depth 100)"), Ok(kint(100)));
+    env.set_max_eval_depth(50);
+    assert!(run(&mut env, "(depth 100)").is_err());
+}
+
+#[test]
+fn test_long_list() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    let numbers: Vec<String> = (0..10000).map(|i| i.to_string()).collect();
+    run(&mut env, &format!("(setq l (list {}))", numbers.join(" "))).unwrap();
+    run(
+        &mut env,
+        "(defun sum (l acc) (if l (sum (cdr l) (+ acc (car l))) acc))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(sum l 0)"), Ok(kint(49995000)));
+    assert_eq!(
+        run(&mut env, &format!("(+ {})", numbers.join(" "))),
+        Ok(kint(49995000))
+    );
+}
+
+#[test]
+fn test_drop_long_list() {
+    let mut list = knil();
+    for i in 0..1000000 {
+        list = kcons(kint(i), list);
+    }
+    drop(list);
+    let mut nested = knil();
+    for _ in 0..1000000 {
+        nested = kcons(nested, knil());
+    }
+    drop(nested);
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((l nil)) (dotimes (i 10000) (setq l (cons i l))) (car l))"
+        ),
+        Ok(kint(9999))
+    );
+}
+
+#[test]
+fn test_argument_order() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq log nil)").unwrap();
+    run(&mut env, "(defun note (x) (setq log (cons x log)) x)").unwrap();
+    run(&mut env, "(list (note 1) (note 2) (note 3))").unwrap();
+    assert_eq!(run(&mut env, "log"), Ok(klist!(kint(3), kint(2), kint(1))));
+}
@@ -0,0 +1,165 @@
+extern crate kappa_lisp;
+use kappa_lisp::expr::Error;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run_new, run_with_limits, Env, Expr, Limit, Limits};
+
+fn format(s: &str) -> String {
+    match run_new(s) {
+        Ok(Expr::Str(ref s)) => s.to_string(),
+        res => panic!("{} returned {:?}", s, res),
+    }
+}
+
+#[test]
+fn test_format_integer() {
+    assert_eq!(format("(format \"%d\" 42)"), "42");
+    assert_eq!(format("(format \"%5d|\" 42)"), "   42|");
+    assert_eq!(format("(format \"%-5d|\" 42)"), "42   |");
+    assert_eq!(format("(format \"%05d\" -42)"), "-0042");
+    assert_eq!(format("(format \"%+d % d\" 42 42)"), "+42  42");
+    assert_eq!(format("(format \"%.3d\" 7)"), "007");
+    assert_eq!(format("(format \"%d %d\" 1.9 -1.9)"), "1 -1");
+    assert_eq!(
+        format("(format \"%d\" (* 99999999999 99999999999))"),
+        "9999999999800000000001"
+    );
+}
+
+#[test]
+fn test_format_radix() {
+    assert_eq!(format("(format \"%o %#o\" 8 8)"), "10 010");
+    assert_eq!(format("(format \"%x %X %#x\" 255 255 255)"), "ff FF 0xff");
+    assert_eq!(format("(format \"%x\" -255)"), "-ff");
+    assert_eq!(format("(format \"%04x %#06x\" 10 10)"), "000a 0x000a");
+    assert_eq!(
+        format("(format \"%x\" #x10000000000000000)"),
+        "10000000000000000"
+    );
+}
+
+#[test]
+fn test_format_char() {
+    assert_eq!(format("(format \"%c%c\" ?a ?あ)"), "aあ");
+    assert_eq!(format("(format \"%3c|%-3c|\" ?a ?b)"), "  a|b  |");
+}
+
+#[test]
+fn test_format_float() {
+    assert_eq!(format("(format \"%f\" 3.14159)"), "3.141590");
+    assert_eq!(format("(format \"%f\" 1)"), "1.000000");
+    assert_eq!(format("(format \"%.2f\" 2.675)"), "2.67");
+    assert_eq!(format("(format \"%08.3f\" -3.14159)"), "-003.142");
+    assert_eq!(format("(format \"%#.0f %.0f\" 3.0 3.0)"), "3. 3");
+    assert_eq!(format("(format \"%e\" 1500.0)"), "1.500000e+03");
+    assert_eq!(format("(format \"%.2e\" 0.000123)"), "1.23e-04");
+    assert_eq!(format("(format \"%g %g\" 0.0001 0.00001)"), "0.0001 1e-05");
+    assert_eq!(format("(format \"%g\" 1234567.0)"), "1.23457e+06");
+    assert_eq!(format("(format \"%g %g %g\" 100.0 1.5 0.0)"), "100 1.5 0");
+    assert_eq!(format("(format \"%#g\" 100.0)"), "100.000");
+    assert_eq!(format("(format \"%.3g\" 3.14159)"), "3.14");
+    assert_eq!(format("(format \"%+.1f\" 2.0)"), "+2.0");
+    assert_eq!(
+        format("(format \"%f %5f\" 1.0e+INF -1.0e+INF)"),
+        "inf  -inf"
+    );
+    assert_eq!(format("(format \"%f\" 0.0e+NaN)"), "nan");
+}
+
+#[test]
+fn test_format_string() {
+    assert_eq!(format("(format \"%.2s\" \"hello\")"), "he");
+    assert_eq!(format("(format \"%-6s|\" 'ab)"), "ab    |");
+    assert_eq!(format("(format \"%5S\" \"a\")"), "  \"a\"");
+    assert_eq!(format("(format \"%s\" 1.5)"), "1.5");
+    assert_eq!(format("(format \"100%%\")"), "100%");
+}
+
+#[test]
+fn test_format_field() {
+    assert_eq!(format("(format \"%2$s %1$s\" \"a\" \"b\")"), "b a");
+    assert_eq!(format("(format \"%1$s %s\" \"a\" \"b\")"), "a b");
+    assert_eq!(format("(format \"%1$05d\" 42)"), "00042");
+    assert_eq!(
+        format("(format \"%s年%s月%s日(%s)\" \"2024\" \"1\" \"2\" \"火\")"),
+        "2024年1月2日(火)"
+    );
+}
+
+#[test]
+fn test_format_error() {
+    let message = |s: &str| {
+        format(&format!(
+            "(condition-case e {} (error (error-message-string e)))",
+            s
+        ))
+    };
+    assert_eq!(
+        message("(format \"%d\" \"a\")"),
+        "Format specifier doesn't match argument type"
+    );
+    assert_eq!(
+        message("(format \"%f\" 'x)"),
+        "Format specifier doesn't match argument type"
+    );
+    assert_eq!(
+        message("(format \"%c\" \"a\")"),
+        "Format specifier doesn't match argument type"
+    );
+    assert_eq!(
+        message("(format \"%s %s\" 1)"),
+        "Not enough arguments for format string"
+    );
+    assert_eq!(message("(format \"%q\" 1)"), "Invalid format operation %q");
+    assert_eq!(
+        message("(format \"%-\")"),
+        "Format string ends in middle of format specifier"
+    );
+    assert_eq!(
+        message("(format \"%999999999999d\" 1)"),
+        "Format width or precision too large"
+    );
+    assert_eq!(
+        message("(format \"%.99999999999f\" 1.0)"),
+        "Format width or precision too large"
+    );
+    assert!(run_new("(format 1)").is_err());
+}
+
+#[test]
+fn test_format_allocation() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    let limits = Limits {
+        max_allocation: Some(100_000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(&mut env, "(format \"%500000d\" 1)", &limits),
+        Err(Error::Limit(Limit::Allocation))
+    );
+    assert_eq!(
+        run_with_limits(&mut env, "(length (format \"%.1000f\" 1.0))", &limits),
+        Ok(kint(1002))
+    );
+}
+
+#[test]
+fn test_format_message() {
+    assert_eq!(
+        format("(format-message \"`%s' is %d\" 'foo 1)"),
+        "\u{2018}foo\u{2019} is 1"
+    );
+    assert_eq!(format("(format-message \"%s\" \"it's\")"), "it's");
+}
+
+#[test]
+fn test_error_format() {
+    assert_eq!(
+        run_new("(condition-case e (error \"bad value: %S\" \"x\") (error (cdr e)))"),
+        Ok(kcons(kstr("bad value: \"x\""), knil()))
+    );
+    assert_eq!(
+        run_new("(condition-case e (error \"can't\") (error (car (cdr e))))"),
+        Ok(kstr("can\u{2019}t"))
+    );
+}
@@ -0,0 +1,237 @@
+extern crate kappa_lisp;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, run_new, Env};
+
+fn new_env() -> Env {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    env
+}
+
+#[test]
+fn test_make_hash_table() {
+    assert_eq!(
+        format!("{}", run_new("(make-hash-table)").unwrap()),
+        "#s(hash-table)"
+    );
+    assert_eq!(
+        format!(
+            "{}",
+            run_new("(make-hash-table :test 'equal :size 10)").unwrap()
+        ),
+        "#s(hash-table test equal)"
+    );
+    assert_eq!(
+        format!("{}", run_new("(make-hash-table :test #'eq)").unwrap()),
+        "#s(hash-table test eq)"
+    );
+    assert_eq!(
+        format!("{}", run_new("(make-hash-table :test #'equal)").unwrap()),
+        "#s(hash-table test equal)"
+    );
+    assert!(run_new("(make-hash-table :test 'car)").is_err());
+    assert!(run_new("(make-hash-table :test #'car)").is_err());
+    assert!(run_new("(make-hash-table :test)").is_err());
+    assert!(run_new("(make-hash-table :foo 1)").is_err());
+}
+
+#[test]
+fn test_gethash_puthash() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table :test 'equal))").unwrap();
+    assert_eq!(
+        run(&mut env, "(puthash \"mile\" 1.6093 h)"),
+        Ok(kfloat(1.6093))
+    );
+    assert_eq!(run(&mut env, "(puthash '(a b) 2 h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(gethash \"mile\" h)"), Ok(kfloat(1.6093)));
+    assert_eq!(run(&mut env, "(gethash (list 'a 'b) h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(gethash \"km\" h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(gethash \"km\" h 0)"), Ok(kint(0)));
+    run(&mut env, "(puthash \"mile\" 1 h)").unwrap();
+    assert_eq!(run(&mut env, "(gethash \"mile\" h)"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(2)));
+}
+
+#[test]
+fn test_hash_test() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table))").unwrap();
+    run(&mut env, "(setq key \"key\")").unwrap();
+    run(&mut env, "(puthash key 1 h)").unwrap();
+    run(&mut env, "(puthash 'sym 2 h)").unwrap();
+    run(&mut env, "(puthash 1.5 3 h)").unwrap();
+    assert_eq!(run(&mut env, "(gethash key h)"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(gethash \"key\" h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(gethash 'sym h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(gethash 1.5 h)"), Ok(kint(3)));
+}
+
+#[test]
+fn test_remhash_clrhash() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table))").unwrap();
+    run(&mut env, "(puthash 1 'one h)").unwrap();
+    run(&mut env, "(puthash 2 'two h)").unwrap();
+    assert_eq!(run(&mut env, "(remhash 1 h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(remhash 3 h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(gethash 1 h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(1)));
+    run(&mut env, "(clrhash h)").unwrap();
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(0)));
+    assert_eq!(run(&mut env, "(gethash 2 h)"), Ok(knil()));
+}
+
+#[test]
+fn test_maphash() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table))").unwrap();
+    run(&mut env, "(puthash 'a 1 h)").unwrap();
+    run(&mut env, "(puthash 'b 2 h)").unwrap();
+    run(&mut env, "(puthash 'c 3 h)").unwrap();
+    run(&mut env, "(setq h2 (make-hash-table))").unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(maphash (lambda (k v) (puthash k (* v 10) h2)) h)"
+        ),
+        Ok(knil())
+    );
+    assert_eq!(
+        format!("{}", run(&mut env, "h2").unwrap()),
+        "#s(hash-table data (a 10 b 20 c 30))"
+    );
+    run(&mut env, "(defun drop (k v) (remhash k h))").unwrap();
+    run(&mut env, "(maphash 'drop h)").unwrap();
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(0)));
+}
+
+#[test]
+fn test_hash_table_literal() {
+    let mut env = new_env();
+    run(
+        &mut env,
+        "(setq h #s(hash-table size 3 test equal data (\"mile\" 1.6093 \"yard\" 1760)))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(gethash \"yard\" h)"), Ok(kint(1760)));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(2)));
+    assert_eq!(
+        format!("{}", run(&mut env, "h").unwrap()),
+        "#s(hash-table test equal data (\"mile\" 1.6093 \"yard\" 1760))"
+    );
+    assert_eq!(
+        run(
+            &mut env,
+            "(equal #s(hash-table data (a 1)) #s(hash-table data (a 1)))"
+        ),
+        Ok(kbool(false))
+    );
+    assert_eq!(run(&mut env, "(equal h h)"), Ok(kbool(true)));
+    assert!(run_new("#s(hash-table test foo)").is_err());
+    assert!(run_new("#s(hash-table data (a))").is_err());
+    assert!(run_new("#s(record a)").is_err());
+}
+
+#[test]
+fn test_eq() {
+    assert_eq!(run_new("(eq 'a 'a)"), Ok(kbool(true)));
+    assert_eq!(run_new("(eq 1 1)"), Ok(kbool(true)));
+    assert_eq!(run_new("(eq \"a\" \"a\")"), Ok(kbool(false)));
+    assert_eq!(run_new("(eq (list 1) (list 1))"), Ok(kbool(false)));
+    assert_eq!(run_new("((lambda (x) (eq x x)) (list 1))"), Ok(kbool(true)));
+    assert_eq!(run_new("(eql 1.5 1.5)"), Ok(kbool(true)));
+    assert_eq!(run_new("(eql 1 1.0)"), Ok(kbool(false)));
+    assert_eq!(run_new("(let ((f #'car)) (eq f f))"), Ok(kbool(true)));
+    assert_eq!(run_new("(eq #'car #'car)"), Ok(kbool(true)));
+    assert_eq!(run_new("(eq #'car #'cdr)"), Ok(kbool(false)));
+    assert_eq!(
+        run_new("(let ((f (lambda (x) x))) (eq f f))"),
+        Ok(kbool(true))
+    );
+    assert_eq!(
+        run_new("(eq (lambda (x) x) (lambda (x) x))"),
+        Ok(kbool(false))
+    );
+    assert_eq!(
+        run_new("(equal (list 1 \"a\") (list 1 \"a\"))"),
+        Ok(kbool(true))
+    );
+}
+
+#[test]
+fn test_equal_deep() {
+    let mut env = new_env();
+    run(&mut env, "(setq a nil)").unwrap();
+    run(&mut env, "(setq b nil)").unwrap();
+    run(
+        &mut env,
+        "(dotimes (i 10000) (setq a (list a)) (setq b (list b)))",
+    )
+    .unwrap();
+    assert_eq!(run(&mut env, "(equal a b)"), Ok(kbool(true)));
+    assert_eq!(run(&mut env, "(equal a (list b))"), Ok(kbool(false)));
+    // a cycle through a list is found
+    run(&mut env, "(setq v (vector nil))").unwrap();
+    run(&mut env, "(setq l (list 1 v))").unwrap();
+    run(&mut env, "(aset v 0 l)").unwrap();
+    run(&mut env, "(setq w (vector nil))").unwrap();
+    run(&mut env, "(setq m (list 1 w))").unwrap();
+    run(&mut env, "(aset w 0 m)").unwrap();
+    assert_eq!(run(&mut env, "(equal l l)"), Ok(kbool(true)));
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case nil (equal l m) (circular-list 'circular))"
+        ),
+        Ok(ksym("circular"))
+    );
+}
+
+#[test]
+fn test_equal_floats() {
+    let mut env = new_env();
+    assert_eq!(run(&mut env, "(equal 0.0 -0.0)"), Ok(kbool(false)));
+    assert_eq!(run(&mut env, "(equal 0.0e+NaN 0.0e+NaN)"), Ok(kbool(true)));
+    assert_eq!(
+        run(&mut env, "(equal (list 1.5 0.0e+NaN) (list 1.5 0.0e+NaN))"),
+        Ok(kbool(true))
+    );
+    // keys are found by the same definition
+    run(&mut env, "(setq h (make-hash-table :test 'equal))").unwrap();
+    run(&mut env, "(puthash 0.0 'zero h)").unwrap();
+    run(&mut env, "(puthash -0.0 'minus h)").unwrap();
+    run(&mut env, "(puthash 0.0e+NaN 'nan h)").unwrap();
+    assert_eq!(run(&mut env, "(gethash 0.0 h)"), Ok(ksym("zero")));
+    assert_eq!(run(&mut env, "(gethash -0.0 h)"), Ok(ksym("minus")));
+    assert_eq!(run(&mut env, "(gethash 0.0e+NaN h)"), Ok(ksym("nan")));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(3)));
+}
+
+#[test]
+fn test_function_keys() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table :test 'eq))").unwrap();
+    run(&mut env, "(setq f (lambda (x) x))").unwrap();
+    run(&mut env, "(puthash #'car 1 h)").unwrap();
+    run(&mut env, "(puthash f 2 h)").unwrap();
+    assert_eq!(run(&mut env, "(gethash #'car h)"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(gethash f h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(gethash #'cdr h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(2)));
+}
+
+#[test]
+fn test_table_in_itself() {
+    let mut env = new_env();
+    run(&mut env, "(setq h (make-hash-table :test 'equal))").unwrap();
+    run(&mut env, "(puthash h 1 h)").unwrap();
+    assert_eq!(run(&mut env, "(puthash h 2 h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(gethash h h)"), Ok(kint(2)));
+    assert_eq!(run(&mut env, "(puthash (vector h) 3 h)"), Ok(kint(3)));
+    assert_eq!(run(&mut env, "(gethash (vector h) h)"), Ok(kint(3)));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(2)));
+    run(&mut env, "(remhash h h)").unwrap();
+    assert_eq!(run(&mut env, "(gethash h h)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(hash-table-count h)"), Ok(kint(1)));
+}
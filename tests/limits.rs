@@ -0,0 +1,214 @@
+extern crate kappa_lisp;
+use kappa_lisp::expr::Error;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, run_with_limits, Env, Limit, Limits};
+use std::thread;
+use std::time::Duration;
+
+fn new_env() -> Env {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    env
+}
+
+#[test]
+fn test_max_steps() {
+    let mut env = new_env();
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(&mut env, "(while t)", &limits),
+        Err(Error::Limit(Limit::Steps))
+    );
+    assert_eq!(run_with_limits(&mut env, "(+ 1 2)", &limits), Ok(kint(3)));
+    // the limits apply only to the call
+    assert_eq!(run(&mut env, "(dotimes (i 1000) i)"), Ok(knil()));
+}
+
+#[test]
+fn test_limits_cannot_be_caught() {
+    let mut env = new_env();
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(&mut env, "(ignore-errors (while t))", &limits),
+        Err(Error::Limit(Limit::Steps))
+    );
+    assert_eq!(
+        run_with_limits(
+            &mut env,
+            "(while t (condition-case nil (while t) (error nil)))",
+            &limits
+        ),
+        Err(Error::Limit(Limit::Steps))
+    );
+}
+
+#[test]
+fn test_limits_skip_cleanup_forms() {
+    let mut env = new_env();
+    run(&mut env, "(setq cleaned nil)").unwrap();
+    let limits = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(
+            &mut env,
+            "(unwind-protect (while t) (setq cleaned t))",
+            &limits
+        ),
+        Err(Error::Limit(Limit::Steps))
+    );
+    assert_eq!(run(&mut env, "cleaned"), Ok(knil()));
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(
+            &mut env,
+            "(unwind-protect (while t) (setq cleaned t))",
+            &limits
+        ),
+        Err(Error::Limit(Limit::Timeout))
+    );
+    assert_eq!(run(&mut env, "cleaned"), Ok(knil()));
+    // the cleanup forms run after errors
+    assert_eq!(
+        run(
+            &mut env,
+            "(ignore-errors (unwind-protect (car 1) (setq cleaned t)))"
+        ),
+        Ok(knil())
+    );
+    assert_eq!(run(&mut env, "cleaned"), Ok(ksym("t")));
+}
+
+#[test]
+fn test_max_depth() {
+    let mut env = new_env();
+    run(&mut env, "(defun f (n) (if (= n 0) 0 (+ 1 (f (- n 1)))))").unwrap();
+    let limits = Limits {
+        max_depth: Some(20),
+        ..Limits::default()
+    };
+    assert_eq!(run_with_limits(&mut env, "(f 5)", &limits), Ok(kint(5)));
+    assert_eq!(
+        run_with_limits(&mut env, "(f 50)", &limits),
+        Err(Error::Limit(Limit::Depth))
+    );
+}
+
+#[test]
+fn test_max_allocation() {
+    let mut env = new_env();
+    let limits = Limits {
+        max_allocation: Some(10000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(
+            &mut env,
+            "(let ((l nil)) (while t (setq l (cons 1 l))))",
+            &limits
+        ),
+        Err(Error::Limit(Limit::Allocation))
+    );
+    assert_eq!(
+        run_with_limits(&mut env, "(make-vector 100000000 nil)", &limits),
+        Err(Error::Limit(Limit::Allocation))
+    );
+    assert_eq!(
+        run_with_limits(&mut env, "(length (make-vector 10 nil))", &limits),
+        Ok(kint(10))
+    );
+}
+
+#[test]
+fn test_max_allocation_of_builders() {
+    let mut env = new_env();
+    let limits = Limits {
+        max_allocation: Some(1 << 20),
+        ..Limits::default()
+    };
+    // without the limit, these would double until the process runs out of memory
+    for doubling in &[
+        "(concat s s)",
+        "(format \"%s%s\" s s)",
+        "(prin1-to-string (list s s))",
+    ] {
+        let program = format!("(let ((s \"ab\")) (while t (setq s {})))", doubling);
+        assert_eq!(
+            run_with_limits(&mut env, &program, &limits),
+            Err(Error::Limit(Limit::Allocation)),
+            "{}",
+            doubling
+        );
+    }
+    assert_eq!(
+        run_with_limits(
+            &mut env,
+            "(let ((v [1])) (while t (setq v (vconcat v v))))",
+            &limits
+        ),
+        Err(Error::Limit(Limit::Allocation))
+    );
+    assert_eq!(
+        run_with_limits(&mut env, "(length (concat \"ab\" \"cd\"))", &limits),
+        Ok(kint(4))
+    );
+}
+
+#[test]
+fn test_timeout() {
+    let mut env = new_env();
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(&mut env, "(while t)", &limits),
+        Err(Error::Limit(Limit::Timeout))
+    );
+}
+
+#[test]
+fn test_interrupt() {
+    let mut env = new_env();
+    let handle = env.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    assert_eq!(
+        run(&mut env, "(while t)"),
+        Err(Error::Limit(Limit::Interrupted))
+    );
+    interrupter.join().unwrap();
+    // the interrupt is consumed
+    assert_eq!(run(&mut env, "(+ 1 2)"), Ok(kint(3)));
+}
+
+#[test]
+fn test_interrupt_skips_cleanup_forms() {
+    let mut env = new_env();
+    let handle = env.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case nil (unwind-protect (while t) (while t)) (error 1))"
+        ),
+        Err(Error::Limit(Limit::Interrupted))
+    );
+    interrupter.join().unwrap();
+    assert_eq!(run(&mut env, "(+ 1 2)"), Ok(kint(3)));
+}
@@ -0,0 +1,186 @@
+#[macro_use]
+extern crate kappa_lisp;
+use kappa_lisp::expr::Error;
+use kappa_lisp::pp::PrettyPrinter;
+use kappa_lisp::read;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, run_new, run_with_limits, Env, Limit, Limits};
+
+fn new_env() -> Env {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    env
+}
+
+fn pp(width: usize, s: &str) -> String {
+    PrettyPrinter::new().width(width).print(&read(s).unwrap())
+}
+
+#[test]
+fn test_pp_fits() {
+    assert_eq!(pp(70, "(a (b . c) \"d\" [e])"), "(a (b . c) \"d\" [e])");
+    assert_eq!(pp(70, "(quote (1 2))"), "'(1 2)");
+}
+
+#[test]
+fn test_pp_special_forms() {
+    assert_eq!(
+        pp(
+            40,
+            "(defun f (x) (let ((y (* x 2))) (if (< y 0) (- y) (+ y 1))))"
+        ),
+        "(defun f (x)
+  (let ((y (* x 2)))
+    (if (< y 0) (- y) (+ y 1))))"
+    );
+    assert_eq!(
+        pp(24, "(if (zerop (% n 2)) (do-even n) (do-odd n))"),
+        "(if (zerop (% n 2))
+    (do-even n)
+  (do-odd n))"
+    );
+    assert_eq!(
+        pp(20, "(lambda (x) (list x x x x x))"),
+        "(lambda (x)
+  (list x x x x x))"
+    );
+}
+
+#[test]
+fn test_pp_data() {
+    assert_eq!(
+        pp(
+            30,
+            "((\"mile\" (\"km\" . 1.6093)) (\"yard\" (\"feet\" . 3) (\"cm\" . 91.44)))"
+        ),
+        "((\"mile\" (\"km\" . 1.6093))
+ (\"yard\"
+  (\"feet\" . 3)
+  (\"cm\" . 91.44)))"
+    );
+    assert_eq!(pp(10, "[1 2 3 4 5 6 7]"), "[1 2 3 4 5\n 6 7]");
+    assert_eq!(pp(12, "(a b c d . e)"), "(a b c d\n   . e)");
+}
+
+#[test]
+fn test_pp_quote() {
+    assert_eq!(
+        pp(
+            20,
+            "(mapcar (function (lambda (x) (* x x))) (quote (1 2 3)))"
+        ),
+        "(mapcar #'(lambda (x)
+            (* x x))
+        '(1 2 3))"
+    );
+}
+
+#[test]
+fn test_pp_indent() {
+    let form = read("(with-foo (a b) (foo a) (bar b))").unwrap();
+    assert_eq!(
+        PrettyPrinter::new().width(20).print(&form),
+        "(with-foo (a b)
+          (foo a)
+          (bar b))"
+    );
+    assert_eq!(
+        PrettyPrinter::new()
+            .width(20)
+            .indent("with-foo", 1)
+            .print(&form),
+        "(with-foo (a b)
+  (foo a)
+  (bar b))"
+    );
+}
+
+#[test]
+fn test_pp_to_string() {
+    let mut env = new_env();
+    assert_eq!(
+        run(&mut env, "(pp-to-string '(a \"b\"))"),
+        Ok(kstr("(a \"b\")\n"))
+    );
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((fill-column 12)) (pp-to-string '(when x (foo) (bar))))"
+        ),
+        Ok(kstr("(when x\n  (foo)\n  (bar))\n"))
+    );
+    assert_eq!(
+        run(&mut env, "(get 'let 'lisp-indent-function)"),
+        Ok(kint(1))
+    );
+    run(&mut env, "(put 'with-foo 'lisp-indent-function 1)").unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((fill-column 16)) (pp-to-string '(with-foo a (foo) (bar))))"
+        ),
+        Ok(kstr("(with-foo a\n  (foo)\n  (bar))\n"))
+    );
+    assert_eq!(run_new("(pp 1)"), Ok(knil()));
+}
+
+#[test]
+fn test_pp_circular() {
+    let mut env = new_env();
+    run(&mut env, "(setq v (vector 'when 'x nil))").unwrap();
+    run(&mut env, "(aset v 2 v)").unwrap();
+    assert_eq!(run(&mut env, "(pp-to-string v)"), Ok(kstr("[when x #0]\n")));
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((fill-column 10)) (pp-to-string (list 'progn v v)))"
+        ),
+        Ok(kstr("(progn\n  [when x\n   #1]\n  [when x\n   #1])\n"))
+    );
+    // the walk over the object counts as evaluation steps
+    run(&mut env, "(setq l nil)").unwrap();
+    run(&mut env, "(dotimes (i 1000) (setq l (cons v l)))").unwrap();
+    let limits = Limits {
+        max_steps: Some(100),
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_with_limits(&mut env, "(pp-to-string l)", &limits),
+        Err(Error::Limit(Limit::Steps))
+    );
+    // so does the layout, which would print 2^30 elements of a shared vector
+    run(&mut env, "(setq w nil)").unwrap();
+    run(&mut env, "(dotimes (i 30) (setq w (vector w w)))").unwrap();
+    let limits = Limits {
+        max_steps: Some(100_000),
+        max_allocation: Some(1 << 20),
+        ..Limits::default()
+    };
+    match run_with_limits(&mut env, "(pp-to-string w)", &limits) {
+        Err(Error::Limit(Limit::Steps)) | Err(Error::Limit(Limit::Allocation)) => (),
+        ret => panic!("unexpected result: {:?}", ret),
+    }
+}
+
+#[test]
+fn test_pp_deep() {
+    let mut nested = kint(1);
+    for _ in 0..100000 {
+        nested = klist!(nested);
+    }
+    let printed = PrettyPrinter::new().print(&nested);
+    assert!(printed.contains("..."));
+}
+
+#[test]
+fn test_pp_read_back() {
+    let env = new_env();
+    let alist = env.find("skk-units-alist").unwrap();
+    for width in 1..40 {
+        let printed = PrettyPrinter::new().width(width).print(&alist);
+        assert_eq!(read(&printed), Ok(alist.clone()), "printed as {}", printed);
+    }
+    let form = klist!(ksym(","), ksym("@x"));
+    let printed = PrettyPrinter::new().width(1).print(&form);
+    assert_eq!(read(&printed), Ok(form));
+}
@@ -0,0 +1,230 @@
+#[macro_use]
+extern crate kappa_lisp;
+use kappa_lisp::expr::Expr;
+use kappa_lisp::hash::{HashTable, HashTest};
+use kappa_lisp::read;
+use kappa_lisp::run_new;
+use kappa_lisp::symbol;
+use kappa_lisp::util::*;
+
+#[test]
+fn test_prin1_string() {
+    let s = kstr("say \"hi\" \\ bye");
+    assert_eq!(s.prin1().to_string(), r#""say \"hi\" \\ bye""#);
+    assert_eq!(s.princ().to_string(), r#"say "hi" \ bye"#);
+    assert_eq!(s.to_string(), s.prin1().to_string());
+    assert_eq!(klist!(kstr("a"), ksym("b")).princ().to_string(), "(a b)");
+}
+
+#[test]
+fn test_prin1_symbol() {
+    let prin1 = |s: &str| ksym(s).prin1().to_string();
+    assert_eq!(prin1("foo"), "foo");
+    assert_eq!(prin1("1+"), "1+");
+    assert_eq!(prin1("12"), "\\12");
+    assert_eq!(prin1("-1.5"), "\\-1.5");
+    assert_eq!(prin1("a b"), "a\\ b");
+    assert_eq!(prin1("(x)"), "\\(x\\)");
+    assert_eq!(prin1("?a"), "\\?a");
+    assert_eq!(prin1("a?"), "a?");
+    assert_eq!(prin1("."), "\\.");
+    assert_eq!(prin1(""), "##");
+    let uninterned = |s: &str| Expr::Sym(symbol::make_symbol(s)).prin1().to_string();
+    assert_eq!(uninterned("foo"), "#:foo");
+    assert_eq!(uninterned("12"), "#:12");
+    assert_eq!(uninterned("a b"), "#:a\\ b");
+    assert_eq!(uninterned(""), "#:");
+    assert_eq!(
+        Expr::Sym(symbol::make_symbol("foo")).princ().to_string(),
+        "foo"
+    );
+    assert_eq!(ksym("a b").princ().to_string(), "a b");
+    assert_eq!(kkw("a;b").prin1().to_string(), r":a\;b");
+}
+
+#[test]
+fn test_prin1_quote() {
+    let print = |s: &str| read(s).unwrap().to_string();
+    assert_eq!(print("(quote x)"), "'x");
+    assert_eq!(print("#'car"), "#'car");
+    assert_eq!(print("`(a ,b ,@c)"), "`(a ,b ,@c)");
+    assert_eq!(print("(\\, @x)"), ", @x");
+    assert_eq!(print("(quote x y)"), "(quote x y)");
+    assert_eq!(print("(quote . x)"), "(quote . x)");
+}
+
+#[test]
+fn test_prin1_to_string() {
+    assert_eq!(
+        run_new(r#"(prin1-to-string "a\"b")"#),
+        Ok(kstr(r#""a\"b""#))
+    );
+    assert_eq!(run_new(r#"(prin1-to-string "a\"b" t)"#), Ok(kstr(r#"a"b"#)));
+    assert_eq!(
+        run_new("(prin1-to-string '(1 2.0 [a \"b\"] . c))"),
+        Ok(kstr("(1 2.0 [a \"b\"] . c)"))
+    );
+    assert_eq!(
+        run_new("(prin1-to-string (intern \"a b\"))"),
+        Ok(kstr("a\\ b"))
+    );
+}
+
+#[test]
+fn test_prin1_circular() {
+    let v = kvector(vec![kint(1), knil()]);
+    if let Expr::Vector(ref cells) = v {
+        cells.borrow_mut()[1] = v.clone();
+    }
+    assert_eq!(v.prin1().to_string(), "[1 #0]");
+    assert_eq!(klist!(kint(0), v.clone()).to_string(), "(0 [1 #1])");
+    assert_eq!(
+        run_new("(let ((h (make-hash-table))) (puthash 'self h h) (format \"%S\" (list h)))"),
+        Ok(kstr("(#s(hash-table data (self #1)))"))
+    );
+}
+
+#[test]
+fn test_prin1_deep() {
+    let mut nested = kint(1);
+    for _ in 0..100000 {
+        nested = klist!(nested);
+    }
+    let printed = nested.prin1().to_string();
+    assert_eq!(
+        printed,
+        format!("{}...{}", "(".repeat(200), ")".repeat(200))
+    );
+    let shallow = (0..199).fold(kint(1), |e, _| klist!(e));
+    assert_eq!(
+        shallow.prin1().to_string(),
+        format!("{}1{}", "(".repeat(199), ")".repeat(199))
+    );
+}
+
+#[test]
+fn test_format_s() {
+    assert_eq!(
+        run_new(r#"(format "%s and %S" "foo" "foo")"#),
+        Ok(kstr(r#"foo and "foo""#))
+    );
+    assert_eq!(
+        run_new(r#"(format "%s%% %s" '(a "b") 1.5)"#),
+        Ok(kstr("(a b)% 1.5"))
+    );
+    assert_eq!(run_new(r#"(format "plain")"#), Ok(kstr("plain")));
+    assert!(run_new(r#"(format "%s")"#).is_err());
+    assert!(run_new(r#"(format "%")"#).is_err());
+    assert!(run_new("(format 'foo)").is_err());
+}
+
+// xorshift, so that the generated objects are the same on every run
+struct Gen(u64);
+
+impl Gen {
+    fn next(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+
+    fn name(&mut self) -> String {
+        const CHARS: &[char] = &[
+            'a', 'z', '0', '9', '-', '+', '.', 'e', '?', '#', ':', '@', ' ', '\n', '(', ')', '[',
+            ']', '"', '\'', ';', '`', ',', '\\', 'あ',
+        ];
+        let len = self.next(5);
+        (0..len)
+            .map(|_| CHARS[self.next(CHARS.len() as u64) as usize])
+            .collect()
+    }
+
+    fn expr(&mut self, depth: u32) -> Expr {
+        let kinds = if depth == 0 { 9 } else { 13 };
+        match self.next(kinds) {
+            0 => Expr::Nil,
+            1 => kint(self.next(2000) as isize - 1000),
+            2 => kint(self.0 as isize),
+            3 => read(&format!("{}000000000000000000", self.next(1000))).unwrap(),
+            4 => {
+                let f = f64::from_bits(self.0);
+                kfloat(if f.is_nan() { f64::INFINITY } else { f })
+            }
+            5 => kstr(self.name()),
+            6 => match self.name() {
+                ref name if name == "nil" => Expr::Nil,
+                name => ksym(name),
+            },
+            7 => kkw(self.name()),
+            8 => Expr::Sym(symbol::make_symbol(&self.name())),
+            9 => {
+                let len = self.next(4);
+                let mut list = if self.next(3) == 0 {
+                    self.expr(depth - 1)
+                } else {
+                    knil()
+                };
+                for _ in 0..len {
+                    list = kcons(self.expr(depth - 1), list);
+                }
+                list
+            }
+            10 => {
+                let quote = ["quote", "function", "`", ",", ",@"][self.next(5) as usize];
+                klist!(ksym(quote), self.expr(depth - 1))
+            }
+            11 => kvector((0..self.next(4)).map(|_| self.expr(depth - 1)).collect()),
+            _ => {
+                let mut table = HashTable::new(HashTest::Equal);
+                for _ in 0..self.next(3) {
+                    let key = self.expr(depth - 1);
+                    let value = self.expr(depth - 1);
+                    table.insert(key, value);
+                }
+                khash_table(table)
+            }
+        }
+    }
+}
+
+// `equal` that compares hash tables by their contents and uninterned symbols by their names
+fn same(x: &Expr, y: &Expr) -> bool {
+    match (x, y) {
+        (Expr::Sym(s1), Expr::Sym(s2)) if !s1.is_interned() || !s2.is_interned() => {
+            s1.is_interned() == s2.is_interned() && s1.name() == s2.name()
+        }
+        (Expr::Cons(car1, cdr1), Expr::Cons(car2, cdr2)) => same(car1, car2) && same(cdr1, cdr2),
+        (Expr::Vector(v1), Expr::Vector(v2)) => {
+            let (v1, v2) = (v1.borrow(), v2.borrow());
+            v1.len() == v2.len() && v1.iter().zip(v2.iter()).all(|(x, y)| same(x, y))
+        }
+        (Expr::HashTable(h1), Expr::HashTable(h2)) => {
+            let (h1, h2) = (h1.borrow(), h2.borrow());
+            let (e1, e2) = (h1.entries(), h2.entries());
+            h1.test() == h2.test()
+                && e1.len() == e2.len()
+                && e1
+                    .iter()
+                    .zip(e2.iter())
+                    .all(|((k1, v1), (k2, v2))| same(k1, k2) && same(v1, v2))
+        }
+        (x, y) => x == y,
+    }
+}
+
+#[test]
+fn test_read_prin1_roundtrip() {
+    let mut gen = Gen(0x2545_f491_4f6c_dd1d);
+    for _ in 0..2000 {
+        let expr = gen.expr(3);
+        let printed = expr.prin1().to_string();
+        let read_back = read(&printed).unwrap();
+        assert!(
+            same(&read_back, &expr),
+            "printed as {}, read as {:?}",
+            printed,
+            read_back
+        );
+    }
+}
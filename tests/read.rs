@@ -1,12 +1,91 @@
 #[macro_use]
 extern crate kappa_lisp;
+use kappa_lisp::expr::{Error, Expr};
 use kappa_lisp::read;
+use kappa_lisp::read::{Position, ReadError, Reason};
 use kappa_lisp::util::*;
 
+fn read_error(s: &str) -> ReadError {
+    match read(s) {
+        Err(Error::Read(e)) => e,
+        res => panic!("expected read error, got {:?}", res),
+    }
+}
+
 #[test]
 fn test_read_empty() {
-    //    assert_eq!(read(""), (keOF));
-    //    assert_eq!(read("(a b"), (keOF));
+    assert_eq!(read_error("").reason, Reason::UnexpectedEof);
+    assert_eq!(read_error("(a b").reason, Reason::UnbalancedParen);
+    assert!(read_error("(a b").is_incomplete());
+}
+
+#[test]
+fn test_read_error_position() {
+    assert_eq!(
+        read_error("(a\n  \"b"),
+        ReadError {
+            position: Position {
+                offset: 7,
+                line: 2,
+                column: 5,
+            },
+            found: None,
+            reason: Reason::UnterminatedString,
+        }
+    );
+    assert_eq!(
+        read_error("(a\n #z)"),
+        ReadError {
+            position: Position {
+                offset: 5,
+                line: 2,
+                column: 3,
+            },
+            found: Some('z'),
+            reason: Reason::UnknownDispatch,
+        }
+    );
+    assert_eq!(
+        read_error("(あ . )").position,
+        Position {
+            offset: 7,
+            line: 1,
+            column: 6,
+        }
+    );
+}
+
+#[test]
+fn test_read_error_reason() {
+    assert_eq!(read_error(")").reason, Reason::UnbalancedParen);
+    assert!(!read_error(")").is_incomplete());
+    assert_eq!(read_error("\"abc").reason, Reason::UnterminatedString);
+    assert_eq!(read_error("#| abc").reason, Reason::UnterminatedComment);
+    assert_eq!(read_error("#z").reason, Reason::UnknownDispatch);
+    assert_eq!(read_error("#").reason, Reason::UnexpectedEof);
+    assert_eq!(read_error("(. a)").reason, Reason::BadDot);
+    assert_eq!(read_error("(a .)").reason, Reason::BadDot);
+    assert_eq!(read_error("(a . b c)").reason, Reason::BadDot);
+    assert_eq!(read_error("(a . b").reason, Reason::UnbalancedParen);
+    assert_eq!(read_error(".").reason, Reason::BadDot);
+    assert_eq!(read_error("\"\\x\"").reason, Reason::InvalidEscape);
+    assert_eq!(read_error("?ab").reason, Reason::InvalidChar);
+    assert_eq!(read_error("?ab").found, Some('b'));
+}
+
+#[test]
+fn test_read_too_deep() {
+    let deep = "(".repeat(100000);
+    assert_eq!(read_error(&deep).reason, Reason::TooDeep);
+    assert!(!read_error(&deep).is_incomplete());
+    assert_eq!(read_error(&"[".repeat(100000)).reason, Reason::TooDeep);
+    assert_eq!(read_error(&"'".repeat(100000)).reason, Reason::TooDeep);
+    assert_eq!(
+        read_error(&format!("({})", "#_".repeat(100000))).reason,
+        Reason::TooDeep
+    );
+    let nested = format!("{}{}", "(".repeat(999), ")".repeat(999));
+    assert!(read(&nested).is_ok());
 }
 
 #[test]
@@ -32,6 +111,96 @@ fn test_read_float() {
     assert_eq!(read("+10.0123"), Ok(kfloat(10.0123)));
 }
 
+#[test]
+fn test_read_float_syntax() {
+    assert_eq!(read("-1.5"), Ok(kfloat(-1.5)));
+    assert_eq!(read("-0.5"), Ok(kfloat(-0.5)));
+    assert_eq!(read(".5"), Ok(kfloat(0.5)));
+    assert_eq!(read("-.5"), Ok(kfloat(-0.5)));
+    assert_eq!(read("1e3"), Ok(kfloat(1000.0)));
+    assert_eq!(read("1.5e2"), Ok(kfloat(150.0)));
+    assert_eq!(read("1.e2"), Ok(kfloat(100.0)));
+    assert_eq!(read("25E-2"), Ok(kfloat(0.25)));
+    assert_eq!(read("1.0e+INF"), Ok(kfloat(f64::INFINITY)));
+    assert_eq!(read("-1.0e+INF"), Ok(kfloat(f64::NEG_INFINITY)));
+    match read("0.0e+NaN") {
+        Ok(Expr::Float(f)) => assert!(f.is_nan()),
+        res => panic!("expected NaN, got {:?}", res),
+    }
+}
+
+#[test]
+fn test_print_float() {
+    let print = |s: &str| read(s).unwrap().to_string();
+    assert_eq!(print("1.0"), "1.0");
+    assert_eq!(print("100.0"), "100.0");
+    assert_eq!(print("1."), "1");
+    assert_eq!(print("0.5"), "0.5");
+    assert_eq!(print("-0.0"), "-0.0");
+    assert_eq!(print("1e20"), "1e+20");
+    assert_eq!(print("1e14"), "100000000000000.0");
+    assert_eq!(print("0.0001"), "0.0001");
+    assert_eq!(print("0.00001"), "1e-05");
+    assert_eq!(print("1.5e-7"), "1.5e-07");
+    assert_eq!(print("0.30000000000000004"), "0.30000000000000004");
+    assert_eq!(print("1.0e+INF"), "1.0e+INF");
+    assert_eq!(print("-1.0e+INF"), "-1.0e+INF");
+    assert_eq!(print("0.0e+NaN"), "0.0e+NaN");
+    // printed floats read back to the same value
+    for s in &[
+        "0.1",
+        "1.6093",
+        "2832.368",
+        "1e300",
+        "5e-324",
+        "123456789.123",
+    ] {
+        assert_eq!(read(&print(s)), read(s));
+    }
+}
+
+#[test]
+fn test_read_int_syntax() {
+    assert_eq!(read("1."), Ok(kint(1)));
+    assert_eq!(read("-1."), Ok(kint(-1)));
+    assert_eq!(read("#x1F"), Ok(kint(31)));
+    assert_eq!(read("#X1f"), Ok(kint(31)));
+    assert_eq!(read("#x-10"), Ok(kint(-16)));
+    assert_eq!(read("#o17"), Ok(kint(15)));
+    assert_eq!(read("#b101"), Ok(kint(5)));
+    assert_eq!(read("#24r1k"), Ok(kint(44)));
+    assert_eq!(read("#2r11"), Ok(kint(3)));
+    assert_eq!(read("(#x10 #b1)"), Ok(klist!(kint(16), kint(1))));
+}
+
+#[test]
+fn test_read_number_like_symbol() {
+    assert_eq!(read("1+"), Ok(ksym("1+")));
+    assert_eq!(read("1-"), Ok(ksym("1-")));
+    assert_eq!(read("+"), Ok(ksym("+")));
+    assert_eq!(read("e10"), Ok(ksym("e10")));
+    assert_eq!(read("1e"), Ok(ksym("1e")));
+    assert_eq!(read("1.2.3"), Ok(ksym("1.2.3")));
+    assert_eq!(read("10abc"), Ok(ksym("10abc")));
+}
+
+#[test]
+fn test_read_invalid_number() {
+    // integers too large for a fixnum are read as bignums
+    assert_eq!(
+        read("99999999999999999999999").unwrap().to_string(),
+        "99999999999999999999999"
+    );
+    assert_eq!(
+        read("#xffffffffffffffffffff").unwrap().to_string(),
+        "1208925819614629174706175"
+    );
+    assert_eq!(read_error("#b102").reason, Reason::InvalidNumber);
+    assert_eq!(read_error("#x").reason, Reason::InvalidNumber);
+    assert_eq!(read_error("#37r1").reason, Reason::InvalidNumber);
+    assert_eq!(read_error("#1x").reason, Reason::UnknownDispatch);
+}
+
 #[test]
 fn test_read_list() {
     assert_eq!(read("()"), Ok(knil()));
@@ -51,11 +220,31 @@ fn test_read_list() {
     );
 }
 
+#[test]
+fn test_read_vector() {
+    assert_eq!(read("[]"), Ok(kvector(vec![])));
+    assert_eq!(
+        read("[1 a [b] (c)]"),
+        Ok(kvector(vec![
+            kint(1),
+            ksym("a"),
+            kvector(vec![ksym("b")]),
+            klist!(ksym("c"))
+        ]))
+    );
+    assert_eq!(read("a[1]"), Ok(ksym("a")));
+    assert_eq!(read_error("[1 2").reason, Reason::UnbalancedParen);
+    assert_eq!(read_error("(1 2]").reason, Reason::UnbalancedParen);
+    assert_eq!(read_error("]").reason, Reason::UnbalancedParen);
+}
+
 #[test]
 fn test_read_symbol() {
     assert_eq!(read("symbol"), Ok(ksym("symbol")));
     assert_eq!(read("+symbol"), Ok(ksym("+symbol")));
     assert_eq!(read("-symbol"), Ok(ksym("-symbol")));
+    assert_eq!(read("-"), Ok(ksym("-")));
+    assert_eq!(read("a.b"), Ok(ksym("a.b")));
     assert_eq!(read("sym-bol"), Ok(ksym("sym-bol")));
     assert_eq!(read("symbol2"), Ok(ksym("symbol2")));
 }
@@ -79,6 +268,105 @@ fn test_read_string() {
     assert_eq!(read("(\"string\")"), Ok(klist!(kstr("string"))));
 }
 
+#[test]
+fn test_read_string_escape() {
+    assert_eq!(read(r#""a\"b""#), Ok(kstr("a\"b")));
+    assert_eq!(read(r#""a\\b""#), Ok(kstr("a\\b")));
+    assert_eq!(read(r#""a\nb\tc""#), Ok(kstr("a\nb\tc")));
+    assert_eq!(read(r#""\a\b\v\f\r\e\s\d""#), Ok(kstr("\x07\x08\x0b\x0c\r\x1b \x7f")));
+    assert_eq!(read(r#""\057""#), Ok(kstr("/")));
+    assert_eq!(read(r#""\0""#), Ok(kstr("\0")));
+    assert_eq!(read(r#""\1011""#), Ok(kstr("A1")));
+    assert_eq!(read(r#""\x41""#), Ok(kstr("A")));
+    assert_eq!(read(r#""\x3042\ a""#), Ok(kstr("あa")));
+    assert_eq!(read(r#""あ""#), Ok(kstr("あ")));
+    assert_eq!(read(r#""\U0001F600""#), Ok(kstr("\u{1F600}")));
+    assert_eq!(read("\"a\\\nb\""), Ok(kstr("ab")));
+    assert_eq!(read(r#""\q""#), Ok(kstr("q")));
+}
+
+#[test]
+fn test_read_string_malformed_escape() {
+    assert!(read(r#""\x""#).is_err());
+    assert!(read(r#""\u30""#).is_err());
+    assert!(read(r#""\U0000304""#).is_err());
+    assert!(read(r#""\UFFFFFFFF""#).is_err());
+    assert!(read(r#""\uD800""#).is_err());
+    assert!(read(r#""abc\"#).is_err());
+}
+
+#[test]
+fn test_read_char() {
+    assert_eq!(read("?a"), Ok(kint(97)));
+    assert_eq!(read("?A"), Ok(kint(65)));
+    assert_eq!(read("?あ"), Ok(kint(0x3042)));
+    assert_eq!(read("?("), Ok(kint(40)));
+    assert_eq!(read(r"?\n"), Ok(kint(10)));
+    assert_eq!(read(r"?\\"), Ok(kint(92)));
+    assert_eq!(read(r"?\s"), Ok(kint(32)));
+    assert_eq!(read(r"?\101"), Ok(kint(65)));
+    assert_eq!(read(r"?\x3042"), Ok(kint(0x3042)));
+    assert_eq!(read(r"?\C-a"), Ok(kint(1)));
+    assert_eq!(read(r"?\C-A"), Ok(kint(1)));
+    assert_eq!(read(r"?\^a"), Ok(kint(1)));
+    assert_eq!(read(r"?\C-?"), Ok(kint(127)));
+    assert_eq!(read(r"?\C-%"), Ok(kint(0x0400_0025)));
+    assert_eq!(read(r"?\M-a"), Ok(kint(0x0800_0061)));
+    assert_eq!(read(r"?\C-\M-a"), Ok(kint(0x0800_0001)));
+    assert_eq!(read(r"?\M-\C-a"), Ok(kint(0x0800_0001)));
+    assert_eq!(read(r"?\s-a"), Ok(kint(0x0080_0061)));
+    assert_eq!(read(r"?\H-a"), Ok(kint(0x0100_0061)));
+    assert_eq!(read(r"?\A-a"), Ok(kint(0x0040_0061)));
+    assert_eq!(read(r"?\S-a"), Ok(kint(0x0200_0061)));
+    assert_eq!(read("(?a ?b)"), Ok(klist!(kint(97), kint(98))));
+    assert!(read("?ab").is_err());
+    assert!(read("?").is_err());
+}
+
+#[test]
+fn test_read_string_control_escape() {
+    assert_eq!(read(r#""\C-a\^b""#), Ok(kstr("\x01\x02")));
+    assert!(read(r#""\M-a""#).is_err());
+}
+
+#[test]
+fn test_read_line_comment() {
+    assert_eq!(read("; comment\n1"), Ok(kint(1)));
+    assert_eq!(read("sym;comment"), Ok(ksym("sym")));
+    assert_eq!(
+        read("(1 ; one\n 2 ; two\n)"),
+        Ok(klist!(kint(1), kint(2)))
+    );
+    assert_eq!(read("(1 ;\n . ;\n 2 ;\n)"), Ok(kcons(kint(1), kint(2))));
+    assert_eq!(read("\"a;b\""), Ok(kstr("a;b")));
+    assert_eq!(read("?;"), Ok(kint(59)));
+    assert!(read("; only a comment").is_err());
+}
+
+#[test]
+fn test_read_block_comment() {
+    assert_eq!(read("#| comment |# 1"), Ok(kint(1)));
+    assert_eq!(read("#| outer #| inner |# outer |# 1"), Ok(kint(1)));
+    assert_eq!(
+        read("(1 #| x |# 2 #| y |#)"),
+        Ok(klist!(kint(1), kint(2)))
+    );
+    assert_eq!(read("(1 #||# . #||# 2)"), Ok(kcons(kint(1), kint(2))));
+    assert!(read("#| unterminated").is_err());
+    assert!(read("#| #| |# 1").is_err());
+}
+
+#[test]
+fn test_read_datum_comment() {
+    assert_eq!(read("#_ 1 2"), Ok(kint(2)));
+    assert_eq!(read("#;(1 2) 3"), Ok(kint(3)));
+    assert_eq!(
+        read("(1 #_(2 3) 4 #;5)"),
+        Ok(klist!(kint(1), kint(4)))
+    );
+    assert_eq!(read("(1 #_ 2 . 3)"), Ok(kcons(kint(1), kint(3))));
+}
+
 #[test]
 fn test_read_quote() {
     assert_eq!(read("'1"), Ok(klist!(ksym("quote"), kint(1))));
@@ -93,6 +381,30 @@ fn test_read_quote() {
     )
 }
 
+#[test]
+fn test_read_backquote() {
+    assert_eq!(read("`a"), Ok(klist!(ksym("`"), ksym("a"))));
+    assert_eq!(
+        read("`(a ,b ,@c)"),
+        Ok(klist!(
+            ksym("`"),
+            klist!(
+                ksym("a"),
+                klist!(ksym(","), ksym("b")),
+                klist!(ksym(",@"), ksym("c"))
+            )
+        ))
+    );
+    assert_eq!(
+        read("(a . ,b)"),
+        Ok(kcons(ksym("a"), klist!(ksym(","), ksym("b"))))
+    );
+    assert_eq!(
+        read("(a,b)"),
+        Ok(klist!(ksym("a"), klist!(ksym(","), ksym("b"))))
+    );
+}
+
 #[test]
 fn test_read_function() {
     assert_eq!(read("#'1"), Ok(klist!(ksym("function"), kint(1))));
@@ -109,3 +421,29 @@ fn test_read_function() {
         Ok(klist!(ksym("function"), klist!(kint(1), kint(2))))
     )
 }
+
+#[test]
+fn test_read_escaped_symbol() {
+    assert_eq!(read("\\12"), Ok(ksym("12")));
+    assert_eq!(read("a\\ b"), Ok(ksym("a b")));
+    assert_eq!(read("\\(x\\)"), Ok(ksym("(x)")));
+    assert_eq!(read("\\?a"), Ok(ksym("?a")));
+    assert_eq!(read("##"), Ok(ksym("")));
+    assert_eq!(read(r":a\;b"), Ok(kkw("a;b")));
+    assert_eq!(read("\\nil"), Ok(knil()));
+    assert!(read_error("a\\").is_incomplete());
+}
+
+#[test]
+fn test_read_uninterned_symbol() {
+    let name = |s: &str| match read(s) {
+        Ok(Expr::Sym(ref sym)) if !sym.is_interned() => sym.name().to_string(),
+        e => panic!("{} read as {:?}", s, e),
+    };
+    assert_eq!(name("#:foo"), "foo");
+    assert_eq!(name("#:12"), "12");
+    assert_eq!(name("#:a\\ b"), "a b");
+    assert_eq!(name("#:"), "");
+    assert!(read("#:foo") != read("#:foo"));
+    assert!(read("#:foo") != read("foo"));
+}
@@ -0,0 +1,124 @@
+#[macro_use]
+extern crate kappa_lisp;
+use std::io::{BufReader, Cursor};
+
+use kappa_lisp::expr::Error;
+use kappa_lisp::read::{Parsed, Reason};
+use kappa_lisp::util::*;
+use kappa_lisp::{ReadBuffer, Reader};
+
+#[test]
+fn test_reader_forms() {
+    let input = Cursor::new("1 (a b)\n; comment\n\"str\"\n  sym");
+    let forms: Vec<_> = Reader::new(input).collect();
+    assert_eq!(
+        forms,
+        vec![
+            Ok(kint(1)),
+            Ok(klist!(ksym("a"), ksym("b"))),
+            Ok(kstr("str")),
+            Ok(ksym("sym")),
+        ]
+    );
+}
+
+#[test]
+fn test_reader_multiline_form() {
+    let input = BufReader::new("(defun f (x)\n  \"doc\n string\"\n  x)\n(f 1)\n".as_bytes());
+    let forms: Vec<_> = Reader::new(input).collect();
+    assert_eq!(forms.len(), 2);
+    assert_eq!(forms[1], Ok(klist!(ksym("f"), kint(1))));
+}
+
+#[test]
+fn test_reader_incomplete_at_eof() {
+    let mut reader = Reader::new(Cursor::new("1 (a b"));
+    assert_eq!(reader.next(), Some(Ok(kint(1))));
+    match reader.next() {
+        Some(Err(Error::Read(e))) => {
+            assert_eq!(e.reason, Reason::UnbalancedParen);
+            assert_eq!(e.position.offset, 6);
+        }
+        res => panic!("unexpected {:?}", res),
+    }
+    assert_eq!(reader.next(), None);
+}
+
+#[test]
+fn test_reader_recovers_after_error() {
+    let forms: Vec<_> = Reader::new(Cursor::new("1 #z\n2\n")).collect();
+    assert_eq!(forms.len(), 3);
+    assert_eq!(forms[0], Ok(kint(1)));
+    assert!(forms[1].is_err());
+    assert_eq!(forms[2], Ok(kint(2)));
+}
+
+#[test]
+fn test_read_buffer_incremental() {
+    let mut buffer = ReadBuffer::new();
+    assert_eq!(buffer.read_next(), Ok(Parsed::Empty));
+    buffer.push_str("(+ 1\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Incomplete));
+    buffer.push_str("   2) foo");
+    assert_eq!(
+        buffer.read_next(),
+        Ok(Parsed::Datum(klist!(ksym("+"), kint(1), kint(2))))
+    );
+    // `foo` may continue in the next chunk
+    assert_eq!(buffer.read_next(), Ok(Parsed::Incomplete));
+    buffer.push_str("bar\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(ksym("foobar"))));
+    assert_eq!(buffer.read_next(), Ok(Parsed::Empty));
+    assert_eq!(buffer.position().line, 3);
+    buffer.push_str("baz");
+    buffer.finish();
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(ksym("baz"))));
+}
+
+#[test]
+fn test_read_buffer_skips_only_broken_datum() {
+    let mut buffer = ReadBuffer::new();
+    buffer.push_str("#z 2 (a #z b) 3");
+    buffer.finish();
+    assert!(buffer.read_next().is_err());
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(kint(2))));
+    assert!(buffer.read_next().is_err());
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(kint(3))));
+    assert_eq!(buffer.read_next(), Ok(Parsed::Empty));
+}
+
+#[test]
+fn test_read_buffer_many_chunks() {
+    let mut buffer = ReadBuffer::new();
+    buffer.push_str("(list");
+    for i in 0..1000 {
+        assert_eq!(buffer.read_next(), Ok(Parsed::Incomplete));
+        buffer.push_str(&format!(" \"a)\\\"\" ?\\( ; )\n#|(|# {}", i));
+    }
+    buffer.push_str(")");
+    match buffer.read_next() {
+        Ok(Parsed::Datum(e)) => {
+            let printed = e.to_string();
+            assert!(printed.starts_with("(list \"a)\\\"\" 40 0 \"a)"));
+            assert!(printed.ends_with(" 40 999)"));
+        }
+        res => panic!("unexpected {:?}", res),
+    }
+}
+
+#[test]
+fn test_read_buffer_datum_comment() {
+    let mut buffer = ReadBuffer::new();
+    buffer.push_str("#;(message \"a\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Incomplete));
+    buffer.push_str("b\")\n");
+    buffer.push_str("(foo)\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(klist!(ksym("foo")))));
+    buffer.push_str("(bar)\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(klist!(ksym("bar")))));
+    buffer.push_str("#_ #_ a\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Incomplete));
+    buffer.push_str("\"b\" c\n");
+    assert_eq!(buffer.read_next(), Ok(Parsed::Datum(ksym("c"))));
+    assert_eq!(buffer.read_next(), Ok(Parsed::Empty));
+}
@@ -0,0 +1,112 @@
+#[macro_use]
+extern crate kappa_lisp;
+use kappa_lisp::util::*;
+use kappa_lisp::{run, EnvBuilder, Profile};
+
+#[test]
+fn test_full_profile() {
+    let mut env = EnvBuilder::new().build().unwrap();
+    assert!(run(&mut env, "(current-time)").is_ok());
+    // skk-num-list is not bound
+    assert!(run(&mut env, "(skk-calc '+)").is_err());
+    assert_eq!(run(&mut env, "(length [1 2])"), Ok(kint(2)));
+}
+
+#[test]
+fn test_pure_profile() {
+    let mut env = EnvBuilder::new().profile(Profile::Pure).build().unwrap();
+    assert_eq!(run(&mut env, "(concat \"a\" \"b\")"), Ok(kstr("ab")));
+    assert_eq!(run(&mut env, "(when t (car '(1)))"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(symbol-name 'foo)"), Ok(kstr("foo")));
+    assert_eq!(run(&mut env, "(prin1-to-string \"a\")"), Ok(kstr("\"a\"")));
+    assert!(run(&mut env, "(current-time)").is_err());
+    assert!(run(&mut env, "(current-time-string)").is_err());
+    assert!(run(&mut env, "(skk-current-date)").is_err());
+    assert_eq!(run(&mut env, "(pp-to-string '(a))"), Ok(kstr("(a)\n")));
+    assert!(run(&mut env, "(pp '(a))").is_err());
+}
+
+#[test]
+fn test_skk_candidate_profile() {
+    let mut env = EnvBuilder::new()
+        .profile(Profile::SkkCandidate)
+        .build()
+        .unwrap();
+    assert_eq!(
+        run(&mut env, "(concat \"a\" (substring \"bcde\" 1 3))"),
+        Ok(kstr("acd"))
+    );
+    assert_eq!(
+        run(&mut env, "(skk-gadget-units-conversion \"mile\" 1 \"km\")"),
+        Ok(kfloat(1.6093))
+    );
+    assert_eq!(
+        run(&mut env, "(format \"%s年%d月\" \"2024\" 1)"),
+        Ok(kstr("2024年1月"))
+    );
+    assert!(run(&mut env, "(current-time-string)").is_ok());
+    assert!(run(&mut env, "(make-hash-table)").is_err());
+    assert!(run(&mut env, "(vector 1 2)").is_err());
+    assert!(run(&mut env, "(defun f () 1)").is_err());
+}
+
+#[test]
+fn test_protect_globals() {
+    let mut env = EnvBuilder::new()
+        .profile(Profile::SkkCandidate)
+        .protect_globals(true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case e (fset 'car 'cdr) (setting-constant e))"
+        ),
+        Ok(klist!(ksym("setting-constant"), ksym("car")))
+    );
+    assert!(run(&mut env, "(fset 'concat (lambda (&rest args) \"\"))").is_err());
+    assert_eq!(run(&mut env, "(concat \"a\" \"b\")"), Ok(kstr("ab")));
+    assert!(run(&mut env, "(setq skk-units-alist nil)").is_err());
+    assert!(run(&mut env, "(defconst skk-units-alist nil)").is_err());
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case e (defvar t) (setting-constant e))"
+        ),
+        Ok(klist!(ksym("setting-constant"), ksym("t")))
+    );
+    // `t` did not turn special, so closures still capture its local bindings
+    assert_eq!(
+        run(&mut env, "(funcall (let ((t 1)) (lambda () t)))"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run(&mut env, "(skk-gadget-units-conversion \"mile\" 1 \"km\")"),
+        Ok(kfloat(1.6093))
+    );
+    // local bindings and new names are allowed
+    assert_eq!(
+        run(&mut env, "(let ((car 1)) (setq car 2) car)"),
+        Ok(kint(2))
+    );
+    assert_eq!(run(&mut env, "(setq x 1)"), Ok(knil()));
+    assert_eq!(run(&mut env, "x"), Ok(kint(1)));
+}
+
+#[test]
+fn test_protect() {
+    let mut env = EnvBuilder::new().protect("answer").build().unwrap();
+    assert!(run(&mut env, "(setq answer 42)").is_err());
+    assert!(run(&mut env, "(defun answer () 42)").is_err());
+    assert!(run(&mut env, "(defun question () 42)").is_ok());
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case e (put 'answer 'p 1) (setting-constant e))"
+        ),
+        Ok(klist!(ksym("setting-constant"), ksym("answer")))
+    );
+    assert!(run(&mut env, "(function-put 'answer 'p 1)").is_err());
+    assert_eq!(run(&mut env, "(get 'answer 'p)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(put 'question 'p 1)"), Ok(kint(1)));
+}
@@ -0,0 +1,113 @@
+extern crate kappa_lisp;
+use kappa_lisp::run_new;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, Env};
+
+#[test]
+fn test_vector_literal() {
+    assert_eq!(
+        run_new("[1 a \"s\"]"),
+        Ok(kvector(vec![kint(1), ksym("a"), kstr("s")]))
+    );
+    assert_eq!(run_new("[]"), Ok(kvector(vec![])));
+    assert_eq!(
+        format!("{}", run_new("[1 (2 3) [4]]").unwrap()),
+        "[1 (2 3) [4]]"
+    );
+}
+
+#[test]
+fn test_vector() {
+    assert_eq!(run_new("(vector)"), Ok(kvector(vec![])));
+    assert_eq!(
+        run_new("(vector 1 (+ 1 1))"),
+        Ok(kvector(vec![kint(1), kint(2)]))
+    );
+    assert_eq!(
+        run_new("(make-vector 2 'x)"),
+        Ok(kvector(vec![ksym("x"), ksym("x")]))
+    );
+    assert!(run_new("(make-vector -1 'x)").is_err());
+}
+
+#[test]
+fn test_aref_aset() {
+    assert_eq!(run_new("(aref [1 2 3] 1)"), Ok(kint(2)));
+    assert_eq!(run_new("(aref \"aあc\" 1)"), Ok(kint(0x3042)));
+    assert!(run_new("(aref [1 2 3] 3)").is_err());
+    assert!(run_new("(aref [1 2 3] -1)").is_err());
+    assert!(run_new("(aref '(1 2 3) 0)").is_err());
+
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq v (make-vector 3 0))").unwrap();
+    assert_eq!(run(&mut env, "(aset v 1 'x)"), Ok(ksym("x")));
+    assert_eq!(
+        run(&mut env, "v"),
+        Ok(kvector(vec![kint(0), ksym("x"), kint(0)]))
+    );
+    assert!(run(&mut env, "(aset v 3 'x)").is_err());
+}
+
+#[test]
+fn test_vconcat() {
+    assert_eq!(run_new("(vconcat)"), Ok(kvector(vec![])));
+    assert_eq!(
+        run_new("(vconcat [1] '(2 3) \"a\" nil)"),
+        Ok(kvector(vec![kint(1), kint(2), kint(3), kint(97)]))
+    );
+    assert!(run_new("(vconcat 1)").is_err());
+}
+
+#[test]
+fn test_length() {
+    assert_eq!(run_new("(length nil)"), Ok(kint(0)));
+    assert_eq!(run_new("(length '(1 2 3))"), Ok(kint(3)));
+    assert_eq!(run_new("(length [1 2])"), Ok(kint(2)));
+    assert_eq!(run_new("(length \"aあ\")"), Ok(kint(2)));
+    assert!(run_new("(length '(1 . 2))").is_err());
+    assert!(run_new("(length 1)").is_err());
+}
+
+#[test]
+fn test_elt() {
+    assert_eq!(run_new("(elt '(1 2 3) 2)"), Ok(kint(3)));
+    assert_eq!(run_new("(elt '(1 2 3) 3)"), Ok(knil()));
+    assert_eq!(run_new("(elt [1 2 3] 0)"), Ok(kint(1)));
+    assert_eq!(run_new("(elt \"abc\" 2)"), Ok(kint(99)));
+    assert!(run_new("(elt [1 2 3] 3)").is_err());
+}
+
+#[test]
+fn test_vector_equal() {
+    assert_eq!(run_new("(equalp [1 (2)] [1 (2)])"), Ok(kbool(true)));
+    assert_eq!(run_new("(equalp [1 2] [1 3])"), Ok(kbool(false)));
+    assert_eq!(run_new("(equalp [1 2] '(1 2))"), Ok(kbool(false)));
+}
+
+#[test]
+fn test_circular_vector_equal() {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    run(&mut env, "(setq v (vector 1 nil))").unwrap();
+    run(&mut env, "(aset v 1 v)").unwrap();
+    run(&mut env, "(setq w (vector 1 nil))").unwrap();
+    run(&mut env, "(aset w 1 w)").unwrap();
+    assert_eq!(run(&mut env, "(equal v v)"), Ok(kbool(true)));
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case nil (equal v w) (circular-list 'circular))"
+        ),
+        Ok(ksym("circular"))
+    );
+    assert!(run(&mut env, "(equalp v w)").is_err());
+}
+
+#[test]
+fn test_vector_backquote() {
+    assert_eq!(
+        run_new("`[a ,(+ 1 2) ,@(list 4 5)]"),
+        Ok(kvector(vec![ksym("a"), kint(3), kint(4), kint(5)]))
+    );
+}
@@ -11,6 +11,14 @@ fn test_skk_calc() {
     assert_eq!(run(&mut env, "(skk-calc '-)"), Ok(kint(1)));
     assert_eq!(run(&mut env, "(skk-calc '*)"), Ok(kint(6)));
     assert_eq!(run(&mut env, "(skk-calc '/)"), Ok(kint(1)));
+    run(&mut env, "(set 'skk-num-list '(3 0))").unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(condition-case nil (skk-calc '/) (arith-error 'zero))"
+        ),
+        Ok(ksym("zero"))
+    );
 }
 
 #[test]
@@ -21,4 +29,13 @@ fn test_skk_gadget_units_conversion() {
         run(&mut env, "(skk-gadget-units-conversion \"mile\" 1 \"km\")"),
         Ok(kfloat(1.6093))
     );
+    assert_eq!(
+        run(
+            &mut env,
+            "(skk-gadget-units-conversion \"mile\" 1760 \"km\")"
+        )
+        .unwrap()
+        .to_string(),
+        "2832.368"
+    );
 }
@@ -0,0 +1,187 @@
+#[macro_use]
+extern crate kappa_lisp;
+use kappa_lisp::symbol;
+use kappa_lisp::util::*;
+use kappa_lisp::{init, run, run_new, Env};
+
+fn new_env() -> Env {
+    let mut env = Env::new();
+    init(&mut env).unwrap();
+    env
+}
+
+#[test]
+fn test_intern() {
+    assert_eq!(run_new("(intern \"foo\")"), Ok(ksym("foo")));
+    assert_eq!(run_new("(eq (intern \"foo\") 'foo)"), Ok(ksym("t")));
+    assert_eq!(run_new("(intern \"nil\")"), Ok(knil()));
+    assert!(run_new("(intern 'foo)").is_err());
+}
+
+#[test]
+fn test_intern_soft() {
+    assert_eq!(run_new("(intern-soft \"car\")"), Ok(ksym("car")));
+    assert_eq!(
+        run_new("(intern-soft \"kappa-never-interned\")"),
+        Ok(knil())
+    );
+    assert_eq!(run_new("(intern-soft 'car)"), Ok(ksym("car")));
+    assert_eq!(run_new("(intern-soft (make-symbol \"car\"))"), Ok(knil()));
+}
+
+#[test]
+fn test_make_symbol() {
+    let mut env = new_env();
+    assert_eq!(run(&mut env, "(eq (make-symbol \"foo\") 'foo)"), Ok(knil()));
+    assert_eq!(
+        run(&mut env, "(let ((s (make-symbol \"foo\"))) (eq s s))"),
+        Ok(ksym("t"))
+    );
+    assert_eq!(
+        run(&mut env, "(symbol-name (make-symbol \"foo\"))"),
+        Ok(kstr("foo"))
+    );
+}
+
+#[test]
+fn test_make_symbol_slots() {
+    let kept = symbol::make_symbol("kept");
+    for i in 0..100 {
+        let sym = symbol::make_symbol(&format!("dropped-{}", i));
+        assert_eq!(sym.name().to_string(), format!("dropped-{}", i));
+    }
+    assert_eq!(kept.name().to_string(), "kept");
+    assert_eq!(kept, kept.clone());
+    assert!(kept != symbol::make_symbol("kept"));
+}
+
+#[test]
+fn test_intern_counts_allocation() {
+    let before = allocated();
+    symbol::intern("kappa-interned-once");
+    let after = allocated();
+    assert!(after > before);
+    symbol::intern("kappa-interned-once");
+    assert_eq!(allocated(), after);
+}
+
+#[test]
+fn test_gensym() {
+    let mut env = new_env();
+    assert_eq!(run(&mut env, "(eq (gensym) (gensym))"), Ok(knil()));
+    assert_eq!(
+        run(&mut env, "(string-to-char (symbol-name (gensym \"tmp\")))"),
+        Ok(kint('t' as isize))
+    );
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((g (gensym))) (eq g (intern (symbol-name g))))"
+        ),
+        Ok(knil())
+    );
+    run(
+        &mut env,
+        "(defmacro my-swap (a b)
+           (let ((tmp (gensym)))
+             `(let ((,tmp ,a)) (setq ,a ,b) (setq ,b ,tmp))))",
+    )
+    .unwrap();
+    assert_eq!(
+        run(
+            &mut env,
+            "(let ((x 1) (tmp 2)) (my-swap x tmp) (list x tmp))"
+        ),
+        Ok(klist!(kint(2), kint(1)))
+    );
+}
+
+#[test]
+fn test_symbol_name() {
+    assert_eq!(run_new("(symbol-name 'foo)"), Ok(kstr("foo")));
+    assert_eq!(run_new("(symbol-name nil)"), Ok(kstr("nil")));
+    assert_eq!(run_new("(symbol-name t)"), Ok(kstr("t")));
+    assert!(run_new("(symbol-name \"foo\")").is_err());
+}
+
+#[test]
+fn test_symbol_value() {
+    let mut env = new_env();
+    run(&mut env, "(defvar kappa-x 1)").unwrap();
+    assert_eq!(run(&mut env, "(symbol-value 'kappa-x)"), Ok(kint(1)));
+    assert_eq!(
+        run(&mut env, "(let ((kappa-x 2)) (symbol-value 'kappa-x))"),
+        Ok(kint(2))
+    );
+    // lexical bindings are not visible
+    assert_eq!(run(&mut env, "(let ((y 2)) (boundp 'y))"), Ok(knil()));
+    assert_eq!(run(&mut env, "(symbol-value nil)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(symbol-value :foo)"), Ok(kkw("foo")));
+    assert!(run(&mut env, "(symbol-value 'kappa-unbound)").is_err());
+}
+
+#[test]
+fn test_boundp() {
+    let mut env = new_env();
+    // declared by skk.lisp without a value
+    assert_eq!(run(&mut env, "(boundp 'skk-num-list)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(boundp 'kappa-y)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(boundp t)"), Ok(ksym("t")));
+    run(&mut env, "(setq kappa-y 1)").unwrap();
+    assert_eq!(run(&mut env, "(boundp 'kappa-y)"), Ok(ksym("t")));
+    assert_eq!(run(&mut env, "(makunbound 'kappa-y)"), Ok(ksym("kappa-y")));
+    assert_eq!(run(&mut env, "(boundp 'kappa-y)"), Ok(knil()));
+}
+
+#[test]
+fn test_symbol_function() {
+    let mut env = new_env();
+    assert_eq!(run(&mut env, "(fboundp 'car)"), Ok(ksym("t")));
+    assert_eq!(run(&mut env, "(fboundp 'kappa-f)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(symbol-function 'kappa-f)"), Ok(knil()));
+    run(&mut env, "(defun kappa-f (x) (* x 2))").unwrap();
+    assert_eq!(
+        run(&mut env, "(funcall (symbol-function 'kappa-f) 3)"),
+        Ok(kint(6))
+    );
+    assert_eq!(run(&mut env, "(fmakunbound 'kappa-f)"), Ok(ksym("kappa-f")));
+    assert_eq!(run(&mut env, "(fboundp 'kappa-f)"), Ok(knil()));
+    assert!(run(&mut env, "(kappa-f 3)").is_err());
+}
+
+#[test]
+fn test_plist() {
+    let mut env = new_env();
+    assert_eq!(run(&mut env, "(get 'kappa-p 'color)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(put 'kappa-p 'color 'red)"), Ok(ksym("red")));
+    run(&mut env, "(put 'kappa-p 'size 3)").unwrap();
+    run(&mut env, "(put 'kappa-p 'color 'blue)").unwrap();
+    assert_eq!(run(&mut env, "(get 'kappa-p 'color)"), Ok(ksym("blue")));
+    assert_eq!(
+        run(&mut env, "(symbol-plist 'kappa-p)"),
+        Ok(klist!(ksym("color"), ksym("blue"), ksym("size"), kint(3)))
+    );
+    // plists belong to the interpreter
+    assert_eq!(run_new("(get 'kappa-p 'color)"), Ok(knil()));
+    assert_eq!(run(&mut env, "(put nil 'p 1)"), Ok(kint(1)));
+    assert_eq!(run(&mut env, "(get nil 'p)"), Ok(kint(1)));
+    assert!(run(&mut env, "(put 1 'p 1)").is_err());
+}
+
+#[test]
+fn test_function_put() {
+    let mut env = new_env();
+    run(
+        &mut env,
+        "(function-put 'kappa-when 'lisp-indent-function 1)",
+    )
+    .unwrap();
+    assert_eq!(
+        run(&mut env, "(function-get 'kappa-when 'lisp-indent-function)"),
+        Ok(kint(1))
+    );
+    assert_eq!(
+        run(&mut env, "(get 'kappa-when 'lisp-indent-function)"),
+        Ok(kint(1))
+    );
+}